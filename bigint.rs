@@ -0,0 +1,438 @@
+#![allow(dead_code)]
+
+// Small arbitrary-precision signed integer, shared by the days that need exact
+// results beyond u64/u128. Magnitude is little-endian base 2^32 with no
+// trailing zero limbs; zero is always non-negative.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct BigInt {
+    neg: bool,
+    mag: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt { neg: false, mag: Vec::new() }
+    }
+
+    pub fn one() -> BigInt {
+        BigInt::from(1u64)
+    }
+
+    fn from_parts(neg: bool, mut mag: Vec<u32>) -> BigInt {
+        while mag.last() == Some(&0) {
+            mag.pop();
+        }
+        let neg = neg && !mag.is_empty();
+        BigInt { neg, mag }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.neg
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt { neg: false, mag: self.mag.clone() }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let mut v: u64 = 0;
+        for &limb in self.mag.iter().rev() {
+            v = (v << 32) | limb as u64;
+        }
+        if self.neg {
+            if v <= i64::MAX as u64 + 1 {
+                Some((v as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else {
+            i64::try_from(v).ok()
+        }
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.neg || self.mag.len() > 2 {
+            return None;
+        }
+        let mut v: u64 = 0;
+        for &limb in self.mag.iter().rev() {
+            v = (v << 32) | limb as u64;
+        }
+        Some(v)
+    }
+
+    pub fn pow(&self, mut exp: u32) -> BigInt {
+        let mut base = self.clone();
+        let mut result = BigInt::one();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = &result * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    // number of significant bits in the magnitude
+    pub fn bits(&self) -> u64 {
+        match self.mag.last() {
+            Some(&top) => self.mag.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    // pow, or None if the result would need more than max_bits bits
+    pub fn checked_pow(&self, exp: u32, max_bits: u64) -> Option<BigInt> {
+        // |a|^e needs more than (bits - 1) * e bits
+        if self.bits() > 1 && (self.bits() - 1).saturating_mul(exp as u64) >= max_bits {
+            return None;
+        }
+        Some(self.pow(exp))
+    }
+
+    // truncating division like the primitive integers: the remainder takes the
+    // sign of the dividend; None on division by zero
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = div_rem_mag(&self.mag, &other.mag);
+        Some((
+            BigInt::from_parts(self.neg != other.neg, q),
+            BigInt::from_parts(self.neg, r),
+        ))
+    }
+
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let (_, r) = a.div_rem(&b).unwrap();
+            a = b;
+            b = r;
+        }
+        a
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for i in (0..a.len()).rev() {
+        match a[i].cmp(&b[i]) {
+            Ordering::Equal => continue,
+            ord => return ord,
+        }
+    }
+    Ordering::Equal
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let s = limb as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(s as u32);
+        carry = s >> 32;
+    }
+    if carry > 0 {
+        out.push(carry as u32);
+    }
+    out
+}
+
+// requires |a| >= |b|
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut d = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        if d < 0 {
+            d += 1 << 32;
+            borrow = 1;
+        } else {
+            borrow = 0;
+        }
+        out.push(d as u32);
+    }
+    out
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = x as u64 * y as u64 + out[i + j] as u64 + carry;
+            out[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    out
+}
+
+fn div_rem_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut q = vec![0u32; a.len()];
+    let mut rem = 0u64;
+    for i in (0..a.len()).rev() {
+        let cur = (rem << 32) | a[i] as u64;
+        q[i] = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    }
+    (q, rem as u32)
+}
+
+// shift-subtract long division; plenty fast for the sizes we see here
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let (q, r) = div_rem_small(a, b[0]);
+        return (q, vec![r]);
+    }
+
+    let mut q = vec![0u32; a.len()];
+    let mut rem: Vec<u32> = Vec::with_capacity(b.len() + 1);
+    for bit in (0..a.len() * 32).rev() {
+        // rem = rem << 1 | bit
+        let mut carry = (a[bit / 32] >> (bit % 32)) & 1;
+        for limb in rem.iter_mut() {
+            let next = *limb >> 31;
+            *limb = (*limb << 1) | carry;
+            carry = next;
+        }
+        if carry > 0 {
+            rem.push(carry);
+        }
+        if cmp_mag(&rem, b) != Ordering::Less {
+            rem = sub_mag(&rem, b);
+            while rem.last() == Some(&0) {
+                rem.pop();
+            }
+            q[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (q, rem)
+}
+
+impl From<u64> for BigInt {
+    fn from(v: u64) -> BigInt {
+        BigInt::from_parts(false, vec![v as u32, (v >> 32) as u32])
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(v: i64) -> BigInt {
+        let m = v.unsigned_abs();
+        BigInt::from_parts(v < 0, vec![m as u32, (m >> 32) as u32])
+    }
+}
+
+impl From<u128> for BigInt {
+    fn from(v: u128) -> BigInt {
+        let mag = (0..4).map(|i| (v >> (32 * i)) as u32).collect();
+        BigInt::from_parts(false, mag)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add<&BigInt> for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, rhs: &BigInt) -> BigInt {
+        if self.neg == rhs.neg {
+            return BigInt::from_parts(self.neg, add_mag(&self.mag, &rhs.mag));
+        }
+        match cmp_mag(&self.mag, &rhs.mag) {
+            Ordering::Less => BigInt::from_parts(rhs.neg, sub_mag(&rhs.mag, &self.mag)),
+            _ => BigInt::from_parts(self.neg, sub_mag(&self.mag, &rhs.mag)),
+        }
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.neg, self.mag.clone())
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.neg, self.mag)
+    }
+}
+
+impl Sub<&BigInt> for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, rhs: &BigInt) -> BigInt {
+        self + &(-rhs)
+    }
+}
+
+impl Mul<&BigInt> for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, rhs: &BigInt) -> BigInt {
+        BigInt::from_parts(self.neg != rhs.neg, mul_mag(&self.mag, &rhs.mag))
+    }
+}
+
+macro_rules! forward_owned {
+    ($tr:ident, $method:ident) => {
+        impl $tr<BigInt> for BigInt {
+            type Output = BigInt;
+
+            fn $method(self, rhs: BigInt) -> BigInt {
+                (&self).$method(&rhs)
+            }
+        }
+
+        impl $tr<&BigInt> for BigInt {
+            type Output = BigInt;
+
+            fn $method(self, rhs: &BigInt) -> BigInt {
+                (&self).$method(rhs)
+            }
+        }
+    };
+}
+
+forward_owned!(Add, add);
+forward_owned!(Sub, sub);
+forward_owned!(Mul, mul);
+
+impl std::iter::Sum for BigInt {
+    fn sum<I: Iterator<Item = BigInt>>(iter: I) -> BigInt {
+        iter.fold(BigInt::zero(), |acc, x| acc + x)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return f.pad("0");
+        }
+        let mut chunks = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = div_rem_small(&mag, 1_000_000_000);
+            chunks.push(r);
+            mag = q;
+            while mag.last() == Some(&0) {
+                mag.pop();
+            }
+        }
+        let mut s = String::new();
+        if self.neg {
+            s.push('-');
+        }
+        s.push_str(&chunks.last().unwrap().to_string());
+        for c in chunks.iter().rev().skip(1) {
+            s.push_str(&format!("{:09}", c));
+        }
+        f.pad(&s)
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(v: i128) -> BigInt {
+        let m = BigInt::from(v.unsigned_abs());
+        if v < 0 { -m } else { m }
+    }
+}
+
+// Cross-checks the arithmetic against i128 on random operands of up to 62
+// bits for products and 126 bits otherwise, so multi-limb long division and
+// carries are exercised.
+pub fn self_check(cases: usize) -> Result<(), String> {
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    let mut operand = |bits: u32| {
+        let raw = ((next() as u128) << 64 | next() as u128) >> (128 - bits);
+        if next() & 1 == 1 { -(raw as i128) } else { raw as i128 }
+    };
+    for case in 0..cases {
+        let bits = [8, 40, 62, 100, 126][case % 5];
+        let (a, b) = (operand(bits), operand(bits));
+        let (ba, bb) = (BigInt::from(a), BigInt::from(b));
+        let fail = |op: &str| Err(format!("bigint {} wrong for {} and {}", op, a, b));
+
+        if (&ba + &bb).to_string() != (a + b).to_string() {
+            return fail("+");
+        }
+        if (&ba - &bb).to_string() != (a - b).to_string() {
+            return fail("-");
+        }
+        if ba.cmp(&bb) != a.cmp(&b) {
+            return fail("cmp");
+        }
+        if ba.to_i64() != i64::try_from(a).ok() {
+            return fail("to_i64");
+        }
+        if bits <= 62 && (&ba * &bb).to_string() != (a * b).to_string() {
+            return fail("*");
+        }
+        if b != 0 {
+            let (q, r) = ba.div_rem(&bb).unwrap();
+            if q.to_string() != (a / b).to_string() || r.to_string() != (a % b).to_string() {
+                return fail("div_rem");
+            }
+        }
+        let (mut x, mut y) = (a.unsigned_abs(), b.unsigned_abs());
+        while y != 0 {
+            (x, y) = (y, x % y);
+        }
+        if ba.gcd(&bb).to_string() != x.to_string() {
+            return fail("gcd");
+        }
+    }
+    Ok(())
+}
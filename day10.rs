@@ -1,392 +1,952 @@
+mod bigint;
+mod linalg;
+
+use linalg::Rational;
+use std::fmt;
 use std::fs;
-use std::time::Instant;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Machine {
     target: Vec<bool>,
     buttons: Vec<Vec<usize>>,
     joltage: Vec<i64>,
 }
 
-fn parse_input(input: &str) -> Vec<Machine> {
-    let mut machines = Vec::new();
-
-    for line in input.lines() {
-        if line.trim().is_empty() {
-            continue;
+// canonical form: [.##.] (3) (1,3) (2) {3,5,4,7}
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lights: String = self.target.iter().map(|&on| if on { '#' } else { '.' }).collect();
+        write!(f, "[{}]", lights)?;
+        for button in &self.buttons {
+            let wiring: Vec<String> = button.iter().map(|i| i.to_string()).collect();
+            write!(f, " ({})", wiring.join(","))?;
         }
+        if !self.joltage.is_empty() {
+            let joltage: Vec<String> = self.joltage.iter().map(|j| j.to_string()).collect();
+            write!(f, " {{{}}}", joltage.join(","))?;
+        }
+        Ok(())
+    }
+}
 
-        // parse indicator lights [.##.]
-        let start = line.find('[').unwrap();
-        let end = line.find(']').unwrap();
-        let lights_str = &line[start + 1..end];
-        let target: Vec<bool> = lights_str.chars().map(|c| c == '#').collect();
-
-        // parse buttons
-        let mut buttons = Vec::new();
-        let rest = &line[end + 1..];
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Open(char),
+    Close(char),
+    Comma,
+    Light(bool),
+    Number(i64),
+}
 
-        let mut i = 0;
-        while i < rest.len() {
-            if rest.chars().nth(i) == Some('(') {
-                let mut j = i + 1;
-                while j < rest.len() && rest.chars().nth(j) != Some(')') {
-                    j += 1;
+// one pass over the line; whitespace is skipped and `//` starts a comment.
+// tokens carry their 1-based column for error messages
+fn tokenize(line: &str) -> Result<Vec<(usize, Token)>, (usize, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().enumerate().peekable();
+    while let Some((i, c)) = chars.next() {
+        let col = i + 1;
+        let token = match c {
+            '[' | '(' | '{' => Token::Open(c),
+            ']' | ')' | '}' => Token::Close(c),
+            ',' => Token::Comma,
+            '.' => Token::Light(false),
+            '#' => Token::Light(true),
+            '/' if chars.peek().map(|&(_, n)| n) == Some('/') => break,
+            '0'..='9' => {
+                let mut value = c.to_digit(10).unwrap() as i64;
+                while let Some(&(_, d)) = chars.peek() {
+                    let Some(digit) = d.to_digit(10) else {
+                        break;
+                    };
+                    value = value.checked_mul(10)
+                        .and_then(|v| v.checked_add(digit as i64))
+                        .ok_or((col, "number too large".to_string()))?;
+                    chars.next();
                 }
-                let button_str = &rest[i + 1..j];
-                let button: Vec<usize> = button_str
-                    .split(',')
-                    .map(|s| s.trim().parse().unwrap())
-                    .collect();
-                buttons.push(button);
-                i = j + 1;
-            } else {
-                i += 1;
+                Token::Number(value)
             }
-        }
-
-        // parse joltage requirements {3,5,4,7}
-        let mut joltage = Vec::new();
-        if let Some(start_brace) = rest.rfind('{') {
-            if let Some(end_brace) = rest.rfind('}') {
-                let joltage_str = &rest[start_brace + 1..end_brace];
-                joltage = joltage_str
-                    .split(',')
-                    .map(|s| s.trim().parse().unwrap())
-                    .collect();
-            }
-        }
-
-        machines.push(Machine { target, buttons, joltage });
+            c if c.is_whitespace() => continue,
+            c => return Err((col, format!("unexpected character '{}'", c))),
+        };
+        tokens.push((col, token));
     }
-
-    return machines;
+    Ok(tokens)
 }
 
-#[inline]
-fn solve_machine(machine: &Machine) -> usize {
-    let n_lights = machine.target.len();
-    let n_buttons = machine.buttons.len();
-    let mut matrix = vec![vec![false; n_buttons + 1]; n_lights];
+// Grammar, sections in any order:
+//   machine := lights button* joltage?
+//   lights  := '[' ('.' | '#')* ']'
+//   button  := '(' (number (',' number)*)? ')'
+//   joltage := '{' number (',' number)* '}'
+// Blank and comment-only lines give None.
+fn parse_machine(line: &str) -> Result<Option<Machine>, (usize, String)> {
+    let tokens = tokenize(line)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let end = line.chars().count() + 1;
+    let mut pos = 0;
+    let mut next = || {
+        let t = tokens.get(pos).copied();
+        pos += 1;
+        t
+    };
 
-    for (button_idx, button) in machine.buttons.iter().enumerate() {
-        for &light_idx in button {
-            if light_idx < n_lights {
-                matrix[light_idx][button_idx] = true;
+    let mut target: Option<Vec<bool>> = None;
+    let mut buttons: Vec<Vec<(usize, i64)>> = Vec::new();
+    let mut joltage: Option<(usize, Vec<i64>)> = None;
+
+    while let Some((col, token)) = next() {
+        match token {
+            Token::Open('[') => {
+                if target.is_some() {
+                    return Err((col, "second light pattern".to_string()));
+                }
+                let mut lights = Vec::new();
+                loop {
+                    match next() {
+                        Some((_, Token::Light(on))) => lights.push(on),
+                        Some((_, Token::Close(']'))) => break,
+                        Some((c, _)) => return Err((c, "expected '.', '#' or ']'".to_string())),
+                        None => return Err((end, "unclosed '['".to_string())),
+                    }
+                }
+                target = Some(lights);
             }
+            Token::Open(open @ ('(' | '{')) => {
+                let close = if open == '(' { ')' } else { '}' };
+                let mut values = Vec::new();
+                loop {
+                    match next() {
+                        Some((_, Token::Close(c))) if c == close && values.is_empty() && open == '(' => break,
+                        Some((c, Token::Number(v))) => values.push((c, v)),
+                        Some((c, _)) => return Err((c, "expected a number".to_string())),
+                        None => return Err((end, format!("unclosed '{}'", open))),
+                    }
+                    match next() {
+                        Some((_, Token::Comma)) => {}
+                        Some((_, Token::Close(c))) if c == close => break,
+                        Some((c, _)) => return Err((c, format!("expected ',' or '{}'", close))),
+                        None => return Err((end, format!("unclosed '{}'", open))),
+                    }
+                }
+                if open == '(' {
+                    buttons.push(values);
+                } else if joltage.is_some() {
+                    return Err((col, "second joltage block".to_string()));
+                } else {
+                    joltage = Some((col, values.into_iter().map(|(_, v)| v).collect()));
+                }
+            }
+            _ => return Err((col, "expected '[', '(' or '{'".to_string())),
         }
     }
 
-    for i in 0..n_lights {
-        matrix[i][n_buttons] = machine.target[i];
+    let Some(target) = target else {
+        return Err((1, "missing light pattern".to_string()));
+    };
+    let Some((joltage_col, joltage)) = joltage else {
+        return Err((end, "missing joltage block".to_string()));
+    };
+    if joltage.len() != target.len() {
+        return Err((joltage_col, format!("{} joltage values, machine has {} lights", joltage.len(), target.len())));
     }
-
-    // gaussian elimination to row echelon form
-    let mut pivot_row = 0;
-    let mut pivot_cols = Vec::new();
-
-    for col in 0..n_buttons {
-        let mut found = false;
-        for row in pivot_row..n_lights {
-            if matrix[row][col] {
-                matrix.swap(pivot_row, row);
-                found = true;
-                break;
+    let mut wired = Vec::new();
+    for values in buttons {
+        let mut button: Vec<usize> = Vec::new();
+        for (col, v) in values {
+            if v as usize >= target.len() {
+                return Err((col, format!("index {} out of range, machine has {} lights", v, target.len())));
+            }
+            if button.contains(&(v as usize)) {
+                return Err((col, format!("index {} listed twice", v)));
             }
+            button.push(v as usize);
         }
+        wired.push(button);
+    }
+    Ok(Some(Machine { target, buttons: wired, joltage }))
+}
 
-        if !found {
-            continue;
+fn parse_input(input: &str) -> Result<Vec<Machine>, String> {
+    let mut machines = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        match parse_machine(line) {
+            Ok(Some(machine)) => machines.push(machine),
+            Ok(None) => {}
+            Err((col, msg)) => return Err(format!("line {}, column {}: {}", i + 1, col, msg)),
         }
+    }
+    Ok(machines)
+}
 
-        pivot_cols.push(col);
+// Per-machine search limits; None means unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+struct Limits {
+    time: Option<Duration>,
+    nodes: Option<u64>,
+}
 
-        for row in 0..n_lights {
-            if row != pivot_row && matrix[row][col] {
-                for c in 0..=n_buttons {
-                    matrix[row][c] ^= matrix[pivot_row][c];
-                }
-            }
-        }
+// Running tally of one machine's search against its limits.
+struct Budget {
+    deadline: Option<Instant>,
+    max_nodes: u64,
+    nodes: u64,
+    // the clock is read on every clock_every-th node
+    clock_every: u64,
+    exhausted: bool,
+}
 
-        pivot_row += 1;
-        if pivot_row >= n_lights {
-            break;
+impl Budget {
+    fn new(limits: Limits, clock_every: u64) -> Budget {
+        Budget {
+            deadline: limits.time.map(|t| Instant::now() + t),
+            max_nodes: limits.nodes.unwrap_or(u64::MAX),
+            nodes: 0,
+            clock_every,
+            exhausted: false,
         }
     }
 
-    // find free variables (columns without pivots)
-    let mut free_vars = Vec::new();
-    for col in 0..n_buttons {
-        if !pivot_cols.contains(&col) {
-            free_vars.push(col);
+    // counts one search node; false, without counting it, once the budget
+    // has run out
+    fn spend(&mut self) -> bool {
+        if self.exhausted {
+            return false;
+        }
+        let check_clock = self.nodes.is_multiple_of(self.clock_every);
+        let late = check_clock && self.deadline.is_some_and(|d| Instant::now() >= d);
+        if self.nodes >= self.max_nodes || late {
+            self.exhausted = true;
+            return false;
         }
+        self.nodes += 1;
+        true
     }
+}
 
-    let num_free = free_vars.len();
-    if num_free > 0 && num_free <= 15 {
-        let mut min_presses = usize::MAX;
-        let mut solution = vec![false; n_buttons];
-
-        for mask in 0..(1 << num_free) {
-            solution.fill(false);
+// A GF(2) system with each row packed into u64 words. Columns 0..cols are
+// the unknowns and column `cols` holds the right-hand side.
+struct Gf2System {
+    cols: usize,
+    rows: Vec<Vec<u64>>,
+}
 
-            for (i, &var) in free_vars.iter().enumerate() {
-                solution[var] = (mask >> i) & 1 == 1;
-            }
+#[inline]
+fn bit(v: &[u64], i: usize) -> bool {
+    (v[i / 64] >> (i % 64)) & 1 == 1
+}
 
-            for i in (0..pivot_cols.len()).rev() {
-                let col = pivot_cols[i];
-                solution[col] = matrix[i][n_buttons];
+#[inline]
+fn toggle(v: &mut [u64], i: usize) {
+    v[i / 64] ^= 1 << (i % 64);
+}
 
-                for c in (col + 1)..n_buttons {
-                    if matrix[i][c] {
-                        solution[col] ^= solution[c];
-                    }
-                }
-            }
+#[inline]
+fn xor_into(dst: &mut [u64], src: &[u64]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
 
-            let presses = solution.iter().filter(|&&x| x).count();
-            min_presses = min_presses.min(presses);
-        }
+#[inline]
+fn weight(v: &[u64]) -> usize {
+    v.iter().map(|w| w.count_ones() as usize).sum()
+}
 
-        return min_presses;
+impl Gf2System {
+    fn new(rows: usize, cols: usize) -> Gf2System {
+        Gf2System { cols, rows: vec![vec![0; (cols + 1).div_ceil(64)]; rows] }
     }
 
-    // no free variables or too many
-    let mut solution = vec![false; n_buttons];
-    for i in (0..pivot_cols.len()).rev() {
-        let col = pivot_cols[i];
-        solution[col] = matrix[i][n_buttons];
-
-        for c in (col + 1)..n_buttons {
-            if matrix[i][c] {
-                solution[col] ^= solution[c];
+    // reduce to RREF in place and return the pivot column of each leading row
+    fn eliminate(&mut self) -> Vec<usize> {
+        let mut pivots = Vec::new();
+        for col in 0..self.cols {
+            let r = pivots.len();
+            let Some(p) = (r..self.rows.len()).find(|&i| bit(&self.rows[i], col)) else {
+                continue;
+            };
+            self.rows.swap(r, p);
+            let pivot_row = self.rows[r].clone();
+            for (i, row) in self.rows.iter_mut().enumerate() {
+                if i != r && bit(row, col) {
+                    xor_into(row, &pivot_row);
+                }
+            }
+            pivots.push(col);
+            if pivots.len() == self.rows.len() {
+                break;
             }
         }
+        pivots
     }
+}
 
-    return solution.iter().filter(|&&x| x).count();
+#[derive(Debug, Clone, PartialEq)]
+enum LightResult {
+    // fewest presses that reach the target and which buttons they are
+    Optimal { presses: usize, pressed: Vec<bool> },
+    // some light can never reach its target state
+    Infeasible,
+    // the budget ran out; `best` is the fewest presses found so far
+    TimedOut { nodes: u64, best: usize },
 }
 
 #[inline]
-fn solve_machine_joltage(machine: &Machine) -> i64 {
-    let n_counters = machine.joltage.len();
+fn solve_machine(machine: &Machine, limits: Limits) -> LightResult {
+    let n_lights = machine.target.len();
     let n_buttons = machine.buttons.len();
-
-    // build coefficient matrix A and target vector b
-    let mut a = vec![vec![0i64; n_buttons]; n_counters];
+    let mut system = Gf2System::new(n_lights, n_buttons);
 
     for (button_idx, button) in machine.buttons.iter().enumerate() {
-        for &counter_idx in button {
-            if counter_idx < n_counters {
-                a[counter_idx][button_idx] = 1;
-            }
+        for &light_idx in button {
+            toggle(&mut system.rows[light_idx], button_idx);
         }
     }
+    for (row, &on) in system.rows.iter_mut().zip(&machine.target) {
+        if on {
+            toggle(row, n_buttons);
+        }
+    }
+
+    let pivots = system.eliminate();
+    // rows past the rank are zero on the left, so a set rhs is 0 = 1
+    if system.rows[pivots.len()..].iter().any(|row| bit(row, n_buttons)) {
+        return LightResult::Infeasible;
+    }
 
-    // use Gaussian elimination with better handling of free variables
-    let mut aug = vec![vec![0i64; n_buttons + 1]; n_counters];
-    for i in 0..n_counters {
-        for j in 0..n_buttons {
-            aug[i][j] = a[i][j];
+    // every solution is the particular one (free variables off) xor some
+    // combination of the nullspace basis, one vector per free variable
+    let words = n_buttons.div_ceil(64).max(1);
+    let mut particular = vec![0u64; words];
+    for (row, &col) in system.rows.iter().zip(&pivots) {
+        if bit(row, n_buttons) {
+            toggle(&mut particular, col);
         }
-        aug[i][n_buttons] = machine.joltage[i];
     }
+    let basis: Vec<Vec<u64>> = (0..n_buttons)
+        .filter(|c| !pivots.contains(c))
+        .map(|free| {
+            let mut v = vec![0u64; words];
+            toggle(&mut v, free);
+            for (row, &col) in system.rows.iter().zip(&pivots) {
+                if bit(row, free) {
+                    toggle(&mut v, col);
+                }
+            }
+            v
+        })
+        .collect();
+
+    let mut best = (weight(&particular), particular.clone());
+    // light nodes are cheap next to reading the clock
+    let mut budget = Budget::new(limits, 256);
+    min_weight(&basis, 0, &mut particular, 0, &mut best, &mut budget);
+    if budget.exhausted {
+        return LightResult::TimedOut { nodes: budget.nodes, best: best.0 };
+    }
+    let pressed = (0..n_buttons).map(|i| bit(&best.1, i)).collect();
+    LightResult::Optimal { presses: best.0, pressed }
+}
 
-    // gaussian elimination to reduced row echelon form
-    let mut pivot_row = 0;
-    let mut pivot_cols = Vec::new();
+// Exact minimum-weight search over the coset. Basis vectors have disjoint
+// free bits and the particular solution has none, so a combination of
+// `chosen` vectors presses at least `chosen` buttons: combinations are only
+// extended while that bound can still beat the best, which keeps this exact
+// however many free variables there are.
+fn min_weight(
+    basis: &[Vec<u64>],
+    from: usize,
+    cur: &mut [u64],
+    chosen: usize,
+    best: &mut (usize, Vec<u64>),
+    budget: &mut Budget,
+) {
+    if !budget.spend() {
+        return;
+    }
+    let w = weight(cur);
+    if w < best.0 {
+        *best = (w, cur.to_vec());
+    }
+    if chosen + 1 >= best.0 {
+        return;
+    }
+    for i in from..basis.len() {
+        xor_into(cur, &basis[i]);
+        min_weight(basis, i + 1, cur, chosen + 1, best, budget);
+        xor_into(cur, &basis[i]);
+    }
+}
 
-    for col in 0..n_buttons {
-        let mut best_row = None;
-        let mut best_val = i64::MAX;
+enum Lp {
+    Optimal(Rational, Vec<Rational>),
+    Infeasible,
+    Unbounded,
+}
 
-        for row in pivot_row..n_counters {
-            if aug[row][col] != 0 && aug[row][col].abs() < best_val {
-                best_row = Some(row);
-                best_val = aug[row][col].abs();
-            }
+// Two-phase tableau simplex for: minimize cost.x subject to rows.x = rhs,
+// x >= 0, in exact rationals. Bland's rule keeps it from cycling.
+fn lp_minimize(rows: &[Vec<Rational>], rhs: &[Rational], cost: &[Rational]) -> Lp {
+    let m = rows.len();
+    let n = cost.len();
+    let width = n + m + 1;
+
+    // phase 1 starts from an artificial basis, which needs rhs >= 0
+    let mut t = vec![vec![Rational::zero(); width]; m];
+    for i in 0..m {
+        let flip = rhs[i].is_negative();
+        for j in 0..n {
+            t[i][j] = if flip { rows[i][j].neg() } else { rows[i][j].clone() };
         }
+        t[i][n + i] = Rational::from(1);
+        t[i][width - 1] = if flip { rhs[i].neg() } else { rhs[i].clone() };
+    }
+    let mut basis: Vec<usize> = (n..n + m).collect();
 
-        if best_row.is_none() {
-            continue;
+    let mut obj = vec![Rational::zero(); width];
+    for row in &t {
+        for j in (0..n).chain([width - 1]) {
+            obj[j] = obj[j].sub(&row[j]);
         }
+    }
+    if !run_simplex(&mut t, &mut obj, &mut basis, n + m) {
+        return Lp::Unbounded;
+    }
+    if !obj[width - 1].is_zero() {
+        return Lp::Infeasible;
+    }
 
-        let best_row = best_row.unwrap();
-        aug.swap(pivot_row, best_row);
-        pivot_cols.push(col);
-
-        let pivot_val = aug[pivot_row][col];
-
-        for row in 0..n_counters {
-            if row != pivot_row && aug[row][col] != 0 {
-                let factor = aug[row][col];
-                for c in 0..=n_buttons {
-                    aug[row][c] = aug[row][c] * pivot_val - aug[pivot_row][c] * factor;
-                }
+    // pivot leftover artificials out of the basis; rows where that is not
+    // possible are redundant and stay at zero
+    for i in 0..m {
+        if basis[i] >= n {
+            if let Some(j) = (0..n).find(|&j| !t[i][j].is_zero()) {
+                pivot(&mut t, &mut obj, &mut basis, i, j);
             }
         }
+    }
 
-        pivot_row += 1;
-        if pivot_row >= n_counters {
-            break;
+    let mut obj = vec![Rational::zero(); width];
+    obj[..n].clone_from_slice(cost);
+    for i in 0..m {
+        if basis[i] < n && !cost[basis[i]].is_zero() {
+            let c = &cost[basis[i]];
+            for j in (0..n).chain([width - 1]) {
+                obj[j] = obj[j].sub(&c.mul(&t[i][j]));
+            }
         }
     }
+    if !run_simplex(&mut t, &mut obj, &mut basis, n) {
+        return Lp::Unbounded;
+    }
 
-    // simplify rows by GCD
-    for row in 0..pivot_row {
-        let mut g = 0i64;
-        for col in 0..=n_buttons {
-            g = gcd(g, aug[row][col].abs());
+    let mut x = vec![Rational::zero(); n];
+    for i in 0..m {
+        if basis[i] < n {
+            x[basis[i]] = t[i][width - 1].clone();
         }
-        if g > 1 {
-            for col in 0..=n_buttons {
-                aug[row][col] /= g;
+    }
+    Lp::Optimal(obj[width - 1].neg(), x)
+}
+
+// pivots until no column below `allowed` has a negative reduced cost;
+// false if the objective is unbounded
+fn run_simplex(t: &mut [Vec<Rational>], obj: &mut [Rational], basis: &mut [usize], allowed: usize) -> bool {
+    let rhs = obj.len() - 1;
+    while let Some(col) = (0..allowed).find(|&j| obj[j].is_negative()) {
+        let mut leave: Option<(Rational, usize, usize)> = None;
+        for (i, row) in t.iter().enumerate() {
+            if !row[col].is_zero() && !row[col].is_negative() {
+                let ratio = row[rhs].div(&row[col]).unwrap();
+                if leave.as_ref().is_none_or(|(r, b, _)| (&ratio, basis[i]) < (r, *b)) {
+                    leave = Some((ratio, basis[i], i));
+                }
             }
         }
+        let Some((_, _, row)) = leave else {
+            return false;
+        };
+        pivot(t, obj, basis, row, col);
     }
+    true
+}
 
-    // find free variables
-    let mut free_vars = Vec::new();
-    for col in 0..n_buttons {
-        if !pivot_cols.contains(&col) {
-            free_vars.push(col);
+fn pivot(t: &mut [Vec<Rational>], obj: &mut [Rational], basis: &mut [usize], row: usize, col: usize) {
+    let p = t[row][col].clone();
+    for v in t[row].iter_mut() {
+        *v = v.div(&p).unwrap();
+    }
+    let pivot_row = t[row].clone();
+    for (i, r) in t.iter_mut().enumerate() {
+        if i != row && !r[col].is_zero() {
+            let f = r[col].clone();
+            for (v, pv) in r.iter_mut().zip(&pivot_row) {
+                if !pv.is_zero() {
+                    *v = v.sub(&f.mul(pv));
+                }
+            }
         }
     }
+    if !obj[col].is_zero() {
+        let f = obj[col].clone();
+        for (v, pv) in obj.iter_mut().zip(&pivot_row) {
+            if !pv.is_zero() {
+                *v = v.sub(&f.mul(pv));
+            }
+        }
+    }
+    basis[row] = col;
+}
 
-    // try different values for free variables to minimize total presses
-    let num_free = free_vars.len();
-
-    if num_free == 0 {
-        let mut solution = vec![0i64; n_buttons];
-
-        for i in (0..pivot_cols.len()).rev() {
-            let col = pivot_cols[i];
-            let pivot_val = aug[i][col];
+#[derive(Debug, Clone, PartialEq)]
+enum JoltageResult {
+    // proven minimum total presses and the presses per button
+    Optimal { presses: i64, counts: Vec<i64> },
+    // no non-negative integer press counts reach the joltage
+    Infeasible,
+    // the budget ran out; `best` is the incumbent, if one was found
+    TimedOut { nodes: u64, best: Option<i64> },
+}
 
-            if pivot_val == 0 {
-                continue;
-            }
+// Branch and bound for: minimize sum(x) subject to A x = joltage, x >= 0
+// integer, where A[counter][button] = 1 when the button bumps that counter.
+// Every node solves the LP relaxation exactly; a node is pruned once the
+// ceiling of its LP optimum cannot beat the best integer solution found.
+struct JoltageSearch<'a> {
+    a: &'a [Vec<i64>],
+    b: &'a [i64],
+    best: Option<(i64, Vec<i64>)>,
+    budget: Budget,
+}
 
-            let mut val = aug[i][n_buttons];
-            for c in (col + 1)..n_buttons {
-                val -= aug[i][c] * solution[c];
+impl JoltageSearch<'_> {
+    // the LP relaxation with lower <= x <= upper, shifted to y = x - lower so
+    // the lower bounds disappear and each upper bound becomes a slack row
+    fn relaxation(&self, lower: &[i64], upper: &[i64]) -> Option<(Rational, Vec<Rational>)> {
+        let n = lower.len();
+        let mut rows = Vec::new();
+        let mut rhs = Vec::new();
+        for (row, &target) in self.a.iter().zip(self.b) {
+            let used: i64 = row.iter().zip(lower).map(|(a, l)| a * l).sum();
+            let mut r: Vec<Rational> = row.iter().map(|&v| Rational::from(v)).collect();
+            r.resize(2 * n, Rational::zero());
+            rows.push(r);
+            rhs.push(Rational::from(target - used));
+        }
+        for j in 0..n {
+            let mut r = vec![Rational::zero(); 2 * n];
+            r[j] = Rational::from(1);
+            r[n + j] = Rational::from(1);
+            rows.push(r);
+            rhs.push(Rational::from(upper[j] - lower[j]));
+        }
+        let mut cost = vec![Rational::from(1); n];
+        cost.resize(2 * n, Rational::zero());
+
+        match lp_minimize(&rows, &rhs, &cost) {
+            Lp::Optimal(value, y) => {
+                let shift: i64 = lower.iter().sum();
+                let x = (0..n).map(|j| y[j].add(&Rational::from(lower[j]))).collect();
+                Some((value.add(&Rational::from(shift)), x))
             }
+            Lp::Infeasible => None,
+            Lp::Unbounded => unreachable!("every variable is bounded"),
+        }
+    }
 
-            if val % pivot_val == 0 {
-                solution[col] = val / pivot_val;
-            } else {
-                return i64::MAX / 2;
-            }
+    fn branch(&mut self, lower: &mut Vec<i64>, upper: &mut Vec<i64>) {
+        if !self.budget.spend() {
+            return;
+        }
+        let Some((value, x)) = self.relaxation(lower, upper) else {
+            return;
+        };
+        // press counts are bounded by the joltage, so these all fit in i64
+        let bound = value.ceil().to_i64().unwrap();
+        if self.best.as_ref().is_some_and(|(best, _)| bound >= *best) {
+            return;
         }
 
-        if solution.iter().all(|&x| x >= 0) {
-            return solution.iter().sum();
-        } else {
-            return solution.iter().map(|&x| x.abs()).sum();
+        let Some(j) = (0..x.len()).find(|&j| !x[j].is_integer()) else {
+            let counts: Vec<i64> = x.iter().map(|v| v.to_i64().unwrap()).collect();
+            self.best = Some((counts.iter().sum(), counts));
+            return;
+        };
+
+        let (down, up) = (x[j].floor().to_i64().unwrap(), x[j].ceil().to_i64().unwrap());
+        let saved = (lower[j], upper[j]);
+        upper[j] = down;
+        self.branch(lower, upper);
+        upper[j] = saved.1;
+        lower[j] = up;
+        self.branch(lower, upper);
+        lower[j] = saved.0;
+    }
+}
+
+#[inline]
+fn solve_machine_joltage(machine: &Machine, limits: Limits) -> JoltageResult {
+    let n_counters = machine.joltage.len();
+    let n_buttons = machine.buttons.len();
+
+    let mut a = vec![vec![0i64; n_buttons]; n_counters];
+    for (button_idx, button) in machine.buttons.iter().enumerate() {
+        for &counter_idx in button {
+            a[counter_idx][button_idx] = 1;
         }
     }
 
-    // try a range of values for free variables
-    let max_val = machine.joltage.iter().max().copied().unwrap_or(100);
-    let search_range = if num_free <= 2 {
-        (max_val * 3).min(200)
-    } else {
-        (max_val * 2).min(100)
+    // an inconsistent system, or one whose solution is unique, is settled
+    // without any search
+    let Some(system) = linalg::solve(&a, &machine.joltage) else {
+        return JoltageResult::Infeasible;
     };
+    if system.rank == n_buttons {
+        let counts: Option<Vec<i64>> = system.particular.iter().map(|v| v.to_i64().filter(|&c| c >= 0)).collect();
+        return match counts {
+            Some(counts) => JoltageResult::Optimal { presses: counts.iter().sum(), counts },
+            None => JoltageResult::Infeasible,
+        };
+    }
 
-    let mut min_cost = i64::MAX;
-    try_free_variables(&aug, &pivot_cols, &free_vars, n_buttons, 0, &mut vec![0; num_free], search_range, &mut min_cost);
-
-    return min_cost;
+    // a button can be pressed at most as often as the smallest counter it
+    // bumps allows; one that bumps nothing is never worth pressing
+    let mut lower = vec![0; n_buttons];
+    let mut upper: Vec<i64> = (0..n_buttons)
+        .map(|j| (0..n_counters).filter(|&i| a[i][j] != 0).map(|i| machine.joltage[i]).min().unwrap_or(0))
+        .collect();
+
+    // every node solves an LP, so the deadline is checked on each one
+    let mut search = JoltageSearch { a: &a, b: &machine.joltage, best: None, budget: Budget::new(limits, 1) };
+    search.branch(&mut lower, &mut upper);
+    if search.budget.exhausted {
+        let best = search.best.map(|(presses, _)| presses);
+        return JoltageResult::TimedOut { nodes: search.budget.nodes, best };
+    }
+    match search.best {
+        Some((presses, counts)) => JoltageResult::Optimal { presses, counts },
+        None => JoltageResult::Infeasible,
+    }
 }
 
-fn try_free_variables(
-    aug: &[Vec<i64>],
-    pivot_cols: &[usize],
-    free_vars: &[usize],
-    n_buttons: usize,
-    free_idx: usize,
-    free_values: &mut Vec<i64>,
-    max_val: i64,
-    min_cost: &mut i64,
-) {
-    if free_idx == free_vars.len() {
-        let mut solution = vec![0i64; n_buttons];
-        for (i, &var) in free_vars.iter().enumerate() {
-            solution[var] = free_values[i];
-        }
-
-        for i in (0..pivot_cols.len()).rev() {
-            let col = pivot_cols[i];
-            let pivot_val = aug[i][col];
+// buttons actually pressed, as (button index, times pressed)
+type Presses = Vec<(usize, i64)>;
 
-            if pivot_val == 0 {
-                continue;
-            }
-
-            let mut val = aug[i][n_buttons];
-            for c in (col + 1)..n_buttons {
-                val -= aug[i][c] * solution[c];
-            }
+fn light_presses(pressed: &[bool]) -> Presses {
+    (0..pressed.len()).filter(|&i| pressed[i]).map(|i| (i, 1)).collect()
+}
 
-            if val % pivot_val != 0 {
-                return; // no valid solution with these free values
-            }
+fn joltage_presses(counts: &[i64]) -> Presses {
+    (0..counts.len()).filter(|&i| counts[i] > 0).map(|i| (i, counts[i])).collect()
+}
 
-            solution[col] = val / pivot_val;
+// replay the presses from all lights off and compare with the target
+fn verify_lights(machine: &Machine, presses: &Presses) -> Result<(), String> {
+    let mut lights = vec![false; machine.target.len()];
+    for &(b, times) in presses {
+        let button = machine.buttons.get(b).ok_or(format!("no button {}", b))?;
+        for &light in button {
+            let l = lights.get_mut(light).ok_or(format!("button {} toggles missing light {}", b, light))?;
+            *l ^= times % 2 == 1;
         }
-
-        if solution.iter().all(|&x| x >= 0) {
-            let cost: i64 = solution.iter().sum();
-            *min_cost = (*min_cost).min(cost);
+    }
+    for (i, (&got, &want)) in lights.iter().zip(&machine.target).enumerate() {
+        if got != want {
+            let state = |on: bool| if on { "on" } else { "off" };
+            return Err(format!("light {} ends {}, expected {}", i, state(got), state(want)));
         }
+    }
+    Ok(())
+}
 
-        return;
+// replay the presses from all counters at zero and compare with the joltage
+fn verify_joltage(machine: &Machine, presses: &Presses) -> Result<(), String> {
+    let mut counters = vec![0i64; machine.joltage.len()];
+    for &(b, times) in presses {
+        if times < 0 {
+            return Err(format!("button {} pressed {} times", b, times));
+        }
+        let button = machine.buttons.get(b).ok_or(format!("no button {}", b))?;
+        for &counter in button {
+            let c = counters.get_mut(counter).ok_or(format!("button {} bumps missing counter {}", b, counter))?;
+            *c = c.checked_add(times).ok_or(format!("counter {} overflows i64", counter))?;
+        }
+    }
+    for (i, (&got, &want)) in counters.iter().zip(&machine.joltage).enumerate() {
+        if got != want {
+            return Err(format!("counter {} ends at {}, expected {}", i, got, want));
+        }
     }
+    Ok(())
+}
 
-    // if current free variable sum already exceeds min_cost, stop
-    let current_sum: i64 = free_values[..free_idx].iter().sum();
-    if current_sum >= *min_cost {
-        return;
+fn format_presses(machine: &Machine, presses: &Presses) -> String {
+    if presses.is_empty() {
+        return "nothing".to_string();
     }
+    presses
+        .iter()
+        .map(|&(b, times)| {
+            let wiring: Vec<String> = machine.buttons[b].iter().map(|i| i.to_string()).collect();
+            format!("#{} ({}) x{}", b, wiring.join(","), times)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-    // try different values for this free variable
-    for val in 0..=max_val {
-        free_values[free_idx] = val;
-        try_free_variables(aug, pivot_cols, free_vars, n_buttons, free_idx + 1, free_values, max_val, min_cost);
+// The joltage problem as a CPLEX LP model, one integer variable x<j> per
+// button, for cross-checking with an external ILP solver.
+fn export_lp(machine: &Machine, label: &str) -> Result<String, String> {
+    // with no variables there is nothing valid to write: LP format needs a
+    // non-empty objective and every name in it declared
+    if machine.buttons.is_empty() {
+        return Err(format!("{}: no buttons, nothing to export", label));
     }
+    let vars: Vec<String> = (0..machine.buttons.len()).map(|j| format!("x{}", j)).collect();
+    let mut out = format!("\\ {}\n\\ {}\nMinimize\n presses: {}\nSubject To\n", label, machine, vars.join(" + "));
+    for (i, &target) in machine.joltage.iter().enumerate() {
+        let terms: Vec<&str> = (0..vars.len())
+            .filter(|&j| machine.buttons[j].contains(&i))
+            .map(|j| vars[j].as_str())
+            .collect();
+        let lhs = if terms.is_empty() { "0 x0".to_string() } else { terms.join(" + ") };
+        out += &format!(" c{}: {} = {}\n", i, lhs, target);
+    }
+    out += "Bounds\n";
+    for v in &vars {
+        out += &format!(" {} >= 0\n", v);
+    }
+    out += &format!("General\n {}\nEnd\n", vars.join(" "));
+    Ok(out)
 }
 
-fn gcd(a: i64, b: i64) -> i64 {
-    if b == 0 {
-        return a;
-    } else {
-        return gcd(b, a % b);
+// Reads press counts from a solver's solution file and checks them against
+// the machine. Any line naming a variable x<j> is read, taking the first
+// number after the name as its value. Markers in between are skipped, which
+// covers "name value", "index name value" and glpsol's "index name * value
+// lower upper" layouts; variables left out are taken as zero, and naming one
+// twice is an error.
+fn import_solution(machine: &Machine, text: &str) -> Result<Vec<i64>, String> {
+    let mut counts = vec![0i64; machine.buttons.len()];
+    let mut seen = vec![false; machine.buttons.len()];
+    for (n, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.first().is_none_or(|w| w.starts_with('#')) {
+            continue;
+        }
+        let Some(k) = words.iter().position(|w| w.strip_prefix('x').is_some_and(|d| d.parse::<usize>().is_ok())) else {
+            continue;
+        };
+        let j: usize = words[k][1..].parse().unwrap();
+        let value: f64 = words[k + 1..].iter().find_map(|v| v.parse().ok())
+            .ok_or(format!("line {}: no value for {}", n + 1, words[k]))?;
+        if j >= counts.len() {
+            return Err(format!("line {}: machine has no button {}", n + 1, j));
+        }
+        if std::mem::replace(&mut seen[j], true) {
+            return Err(format!("line {}: {} appears twice", n + 1, words[k]));
+        }
+        // i64::MAX as f64 rounds up to 2^63, which is already out of range
+        if !value.is_finite() || value >= i64::MAX as f64 {
+            return Err(format!("line {}: {} = {} is out of range", n + 1, words[k], value));
+        }
+        let rounded = value.round();
+        if (value - rounded).abs() > 1e-6 || rounded < 0.0 {
+            return Err(format!("line {}: {} = {} is not a non-negative integer", n + 1, words[k], value));
+        }
+        counts[j] = rounded as i64;
     }
+    verify_joltage(machine, &joltage_presses(&counts))?;
+    Ok(counts)
+}
+
+// Solves every machine on `threads` workers pulling indices from a shared
+// counter, so one slow machine only ties up its own worker. Results come
+// back in input order.
+fn solve_all<T, F>(machines: &[Machine], threads: usize, limits: Limits, solve: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Machine, Limits) -> T + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..machines.len()).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(machine) = machines.get(i) else {
+                    break;
+                };
+                let result = solve(machine, limits);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(|r| r.unwrap()).collect()
 }
 
 #[inline]
-fn part1(machines: &[Machine]) -> usize {
-    return machines.iter().map(|m| solve_machine(m)).sum();
+fn part1(machines: &[Machine], threads: usize, limits: Limits) -> Vec<LightResult> {
+    return solve_all(machines, threads, limits, solve_machine);
 }
 
 #[inline]
-fn part2(machines: &[Machine]) -> i64 {
-    return machines.iter().map(|m| solve_machine_joltage(m)).sum();
+fn part2(machines: &[Machine], threads: usize, limits: Limits) -> Vec<JoltageResult> {
+    return solve_all(machines, threads, limits, solve_machine_joltage);
+}
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(String::as_str)
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let show_presses = args.iter().any(|a| a == "--presses");
+
+    // --check tests the shared exact arithmetic against i128 first, outside
+    // the Part 1 timing
+    if args.iter().any(|a| a == "--check") {
+        let start = Instant::now();
+        let bigint_check = (bigint::self_check(20_000), start.elapsed());
+        let start = Instant::now();
+        let linalg_check = (linalg::self_check(5_000), start.elapsed());
+        for (name, (result, elapsed)) in [("bigint", bigint_check), ("linalg", linalg_check)] {
+            match result {
+                Ok(()) => println!("{} check: matches i128 ({:?})", name, elapsed),
+                Err(e) => println!("{} check: MISMATCH, {}", name, e),
+            }
+        }
+    }
+
     let start = Instant::now();
     let input = fs::read_to_string("inputs/day10_in.txt")?;
-    let machines = parse_input(&input);
-    let part1_res = part1(&machines);
-    println!("Part 1: {} ({:?})", part1_res, start.elapsed());
+    let machines = parse_input(&input)?;
+
+    // print the input back in canonical form, checking it parses to the same
+    if args.iter().any(|a| a == "--format") {
+        for machine in &machines {
+            let line = machine.to_string();
+            assert_eq!(parse_machine(&line).ok().flatten().as_ref(), Some(machine), "round trip: {}", line);
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    // --export-lp <dir> writes machine_<n>.lp per machine for an external
+    // solver; --import <file> --machine <n> checks its solution file
+    if let Some(dir) = arg_value(&args, "--export-lp") {
+        fs::create_dir_all(dir)?;
+        let mut written = 0;
+        for (i, machine) in machines.iter().enumerate() {
+            match export_lp(machine, &format!("day10 machine {}", i + 1)) {
+                Ok(lp) => {
+                    fs::write(format!("{}/machine_{}.lp", dir, i + 1), lp)?;
+                    written += 1;
+                }
+                Err(e) => println!("skipped {}", e),
+            }
+        }
+        println!("Wrote {} LP models to {}", written, dir);
+        return Ok(());
+    }
+    if let Some(path) = arg_value(&args, "--import") {
+        let n: usize = arg_value(&args, "--machine").ok_or("--import needs --machine <n>")?.parse()?;
+        let machine = machines.get(n.wrapping_sub(1)).ok_or(format!("no machine {}", n))?;
+        let counts = import_solution(machine, &fs::read_to_string(path)?)?;
+        let presses = counts.iter().try_fold(0i64, |total, &c| total.checked_add(c)).ok_or("press total overflows i64")?;
+        println!("machine {}: solution is valid, {} presses", n, presses);
+        match solve_machine_joltage(machine, Limits::default()) {
+            JoltageResult::Optimal { presses: ours, .. } if ours == presses => println!("matches our optimum"),
+            JoltageResult::Optimal { presses: ours, .. } => println!("MISMATCH: our optimum is {} presses", ours),
+            other => println!("MISMATCH: our solver gives {:?}", other),
+        }
+        return Ok(());
+    }
+
+    // machines are solved in parallel on --threads workers (default: all
+    // cores), each giving up after --timeout-ms or --max-nodes search nodes
+    let threads = match arg_value(&args, "--threads") {
+        Some(v) => v.parse()?,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let limits = Limits {
+        time: arg_value(&args, "--timeout-ms").map(str::parse).transpose()?.map(Duration::from_millis),
+        nodes: arg_value(&args, "--max-nodes").map(str::parse).transpose()?,
+    };
+
+    let results = part1(&machines, threads, limits);
+    let (mut part1_res, mut solved, mut timed_out) = (0, 0, 0);
+    let mut notes = Vec::new();
+    for (i, (machine, result)) in machines.iter().zip(&results).enumerate() {
+        match result {
+            LightResult::Optimal { presses, pressed } => {
+                part1_res += presses;
+                solved += 1;
+                let presses = light_presses(pressed);
+                if let Err(e) = verify_lights(machine, &presses) {
+                    notes.push(format!("  machine {} presses FAIL verification: {}", i + 1, e));
+                }
+                if show_presses {
+                    notes.push(format!("  machine {}: {}", i + 1, format_presses(machine, &presses)));
+                }
+            }
+            LightResult::Infeasible => {
+                notes.push(format!("  machine {} cannot reach its light pattern", i + 1));
+            }
+            LightResult::TimedOut { nodes, best } => {
+                timed_out += 1;
+                notes.push(format!("  machine {} timed out after {} nodes, best so far {} presses", i + 1, nodes, best));
+            }
+        }
+    }
+    println!(
+        "Part 1: {} ({} solved, {} timed out, {} infeasible) ({:?})",
+        part1_res, solved, timed_out, results.len() - solved - timed_out, start.elapsed()
+    );
+    for note in notes {
+        println!("{}", note);
+    }
 
     let start = Instant::now();
-    let part2_res = part2(&machines);
-    println!("Part 2: {} ({:?})", part2_res, start.elapsed());
+    let results = part2(&machines, threads, limits);
+    let (mut part2_res, mut solved, mut timed_out) = (0, 0, 0);
+    let mut notes = Vec::new();
+    for (i, (machine, result)) in machines.iter().zip(&results).enumerate() {
+        match result {
+            JoltageResult::Optimal { presses, counts } => {
+                part2_res += presses;
+                solved += 1;
+                let presses = joltage_presses(counts);
+                if let Err(e) = verify_joltage(machine, &presses) {
+                    notes.push(format!("  machine {} presses FAIL verification: {}", i + 1, e));
+                }
+                if show_presses {
+                    notes.push(format!("  machine {}: {}", i + 1, format_presses(machine, &presses)));
+                }
+            }
+            JoltageResult::Infeasible => {
+                notes.push(format!("  machine {} cannot reach its joltage", i + 1));
+            }
+            JoltageResult::TimedOut { nodes, best } => {
+                timed_out += 1;
+                let best = best.map_or("none".to_string(), |b| format!("{} presses", b));
+                notes.push(format!("  machine {} timed out after {} nodes, best so far {}", i + 1, nodes, best));
+            }
+        }
+    }
+    println!(
+        "Part 2: {} ({} proven optimal, {} timed out, {} infeasible) ({:?})",
+        part2_res, solved, timed_out, results.len() - solved - timed_out, start.elapsed()
+    );
+    for note in notes {
+        println!("{}", note);
+    }
 
     Ok(())
 }
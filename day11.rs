@@ -1,71 +1,445 @@
+mod bigint;
+
+use bigint::BigInt;
+use std::fmt;
 use std::fs;
 use std::time::Instant;
 use std::collections::HashMap;
 
-fn dp_count_paths(
-    graph: &HashMap<String, Vec<String>>,
-    node: &str,
-    target: &str,
-    has_dac: bool,
-    has_fft: bool,
-    memo: &mut HashMap<(String, bool, bool), u64>,
-    check_has: bool,
-) -> u64 {
-    let key = (node.to_string(), has_dac, has_fft);
-    if let Some(&cached) = memo.get(&key) {
-        return cached;
+// Device graph with names interned to dense u32 ids and the adjacency in CSR
+// form: the outputs of device u are targets[offsets[u]..offsets[u + 1]].
+struct Graph {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    offsets: Vec<u32>,
+    targets: Vec<u32>,
+}
+
+impl Graph {
+    // builds the CSR layout with a counting sort of the edges by source,
+    // keeping each device's output order
+    fn from_edges(names: Vec<String>, ids: HashMap<String, u32>, edges: &[(u32, u32)]) -> Graph {
+        let mut offsets = vec![0u32; names.len() + 1];
+        for &(u, _) in edges {
+            offsets[u as usize + 1] += 1;
+        }
+        for i in 0..names.len() {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut fill = offsets.clone();
+        let mut targets = vec![0u32; edges.len()];
+        for &(u, v) in edges {
+            targets[fill[u as usize] as usize] = v;
+            fill[u as usize] += 1;
+        }
+        Graph { names, ids, offsets, targets }
+    }
+
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
     }
-    if node == target {
-        let result = if (has_dac && has_fft) || !check_has { 1 } else { 0 };
-        memo.insert(key, result);
-        return result;
+
+    #[inline]
+    fn outputs(&self, u: u32) -> &[u32] {
+        let u = u as usize;
+        &self.targets[self.offsets[u] as usize..self.offsets[u + 1] as usize]
     }
-    let mut total = 0;
-    if let Some(neighbors) = graph.get(node) {
-        for neighbor in neighbors {
-            let next_has_dac = has_dac || neighbor == "dac";
-            let next_has_fft = has_fft || neighbor == "fft";
-            total += dp_count_paths(graph, neighbor, target, next_has_dac, next_has_fft, memo, check_has);
+}
+
+// Topological order by iterative DFS, or the devices along a cycle (first
+// and last the same) if there is one
+fn topological_order(graph: &Graph) -> Result<Vec<u32>, Vec<u32>> {
+    // 0 = unvisited, 1 = on the DFS stack, 2 = finished
+    let mut state = vec![0u8; graph.len()];
+    let mut post = Vec::with_capacity(graph.len());
+    let mut stack: Vec<(u32, usize)> = Vec::new();
+    for root in 0..graph.len() as u32 {
+        if state[root as usize] != 0 {
+            continue;
+        }
+        state[root as usize] = 1;
+        stack.push((root, 0));
+        while let Some(&(u, i)) = stack.last() {
+            let Some(&v) = graph.outputs(u).get(i) else {
+                state[u as usize] = 2;
+                post.push(u);
+                stack.pop();
+                continue;
+            };
+            stack.last_mut().unwrap().1 += 1;
+            match state[v as usize] {
+                0 => {
+                    state[v as usize] = 1;
+                    stack.push((v, 0));
+                }
+                1 => {
+                    let start = stack.iter().position(|&(w, _)| w == v).unwrap();
+                    let mut chain: Vec<u32> = stack[start..].iter().map(|&(w, _)| w).collect();
+                    chain.push(v);
+                    return Err(chain);
+                }
+                _ => {}
+            }
         }
     }
-    memo.insert(key, total);
-    return total;
+    post.reverse();
+    Ok(post)
 }
 
-fn part1_count_paths(graph: &HashMap<String, Vec<String>>) -> u64 {
-    let mut memo = HashMap::new();
-    return dp_count_paths(graph, "you", "out", false, false, &mut memo, false);
+// Strongly connected components by iterative Tarjan: the component of each
+// device and the number of components
+fn strongly_connected(graph: &Graph) -> (Vec<u32>, usize) {
+    let n = graph.len();
+    let mut index = vec![u32::MAX; n];
+    let mut low = vec![0u32; n];
+    let mut on_stack = vec![false; n];
+    let mut stack: Vec<u32> = Vec::new();
+    let mut comp = vec![u32::MAX; n];
+    let (mut next, mut count) = (0u32, 0usize);
+
+    for root in 0..n as u32 {
+        if index[root as usize] != u32::MAX {
+            continue;
+        }
+        let mut call: Vec<(u32, usize)> = vec![(root, 0)];
+        index[root as usize] = next;
+        low[root as usize] = next;
+        next += 1;
+        stack.push(root);
+        on_stack[root as usize] = true;
+
+        while let Some(&(u, i)) = call.last() {
+            let (u, ui) = (u, u as usize);
+            if let Some(&v) = graph.outputs(u).get(i) {
+                call.last_mut().unwrap().1 += 1;
+                let vi = v as usize;
+                if index[vi] == u32::MAX {
+                    index[vi] = next;
+                    low[vi] = next;
+                    next += 1;
+                    stack.push(v);
+                    on_stack[vi] = true;
+                    call.push((v, 0));
+                } else if on_stack[vi] {
+                    low[ui] = low[ui].min(index[vi]);
+                }
+                continue;
+            }
+            call.pop();
+            if let Some(&(p, _)) = call.last() {
+                low[p as usize] = low[p as usize].min(low[ui]);
+            }
+            if low[ui] == index[ui] {
+                while let Some(w) = stack.pop() {
+                    on_stack[w as usize] = false;
+                    comp[w as usize] = count as u32;
+                    if w == u {
+                        break;
+                    }
+                }
+                count += 1;
+            }
+        }
+    }
+    (comp, count)
 }
 
-fn part2_count_paths_with_dac_fft(graph: &HashMap<String, Vec<String>>) -> u64 {
-    let mut memo = HashMap::new();
-    return dp_count_paths(graph, "svr", "out", false, false, &mut memo, true);
+// Collapses every strongly connected component into a single device, named
+// after its members, so the result is a DAG. Every original name resolves to
+// its component. Edges between components keep their multiplicity, since
+// parallel wires are separate paths. Also returns the components that
+// contain a cycle.
+fn condense(graph: &Graph) -> (Graph, Vec<u32>) {
+    let (comp, count) = strongly_connected(graph);
+    let mut members: Vec<Vec<&str>> = vec![Vec::new(); count];
+    for (v, name) in graph.names.iter().enumerate() {
+        members[comp[v] as usize].push(name);
+    }
+    let names: Vec<String> = members
+        .iter_mut()
+        .map(|m| {
+            m.sort_unstable();
+            if m.len() == 1 { m[0].to_string() } else { format!("{{{}}}", m.join(" ")) }
+        })
+        .collect();
+    let ids = graph.ids.iter().map(|(name, &v)| (name.clone(), comp[v as usize])).collect();
+
+    let mut cyclic = vec![false; count];
+    let mut edges = Vec::new();
+    for u in 0..graph.len() as u32 {
+        let cu = comp[u as usize];
+        for &v in graph.outputs(u) {
+            let cv = comp[v as usize];
+            if cu == cv {
+                cyclic[cu as usize] = true;
+            } else {
+                edges.push((cu, cv));
+            }
+        }
+    }
+    let cyclic = (0..count as u32).filter(|&c| cyclic[c as usize]).collect();
+    (Graph::from_edges(names, ids, &edges), cyclic)
 }
 
-fn parse_input(input: &str) -> HashMap<String, Vec<String>> {
-    let mut graph = HashMap::new();
+fn describe_chain(graph: &Graph, chain: &[u32]) -> String {
+    chain.iter().map(|&v| graph.names[v as usize].as_str()).collect::<Vec<_>>().join(" -> ")
+}
 
-    for line in input.lines() {
-        if line.trim().is_empty() {
+// A path-counting question: paths from source to target that pass through
+// every required device, avoid every forbidden one, and meet the devices in
+// `order` in that sequence (those count as required too).
+#[derive(Clone)]
+struct Query {
+    source: u32,
+    target: u32,
+    required: Vec<u32>,
+    forbidden: Vec<u32>,
+    order: Vec<u32>,
+}
+
+// the per-device state table is devices * 2^required entries
+const MAX_REQUIRED: usize = 16;
+
+impl Query {
+    fn new(graph: &Graph, source: &str, target: &str) -> Result<Query, String> {
+        Ok(Query {
+            source: device(graph, source)?,
+            target: device(graph, target)?,
+            required: Vec::new(),
+            forbidden: Vec::new(),
+            order: Vec::new(),
+        })
+    }
+
+    // required and ordered devices together, each once
+    fn all_required(&self) -> Vec<u32> {
+        let mut all: Vec<u32> = Vec::new();
+        for &v in self.required.iter().chain(&self.order) {
+            if !all.contains(&v) {
+                all.push(v);
+            }
+        }
+        all
+    }
+}
+
+fn device(graph: &Graph, name: &str) -> Result<u32, String> {
+    graph.id(name).ok_or(format!("unknown device '{}'", name))
+}
+
+fn devices(graph: &Graph, list: &str) -> Result<Vec<u32>, String> {
+    list.split(',').filter(|s| !s.is_empty()).map(|name| device(graph, name.trim())).collect()
+}
+
+// Path counters: u128 for speed, BigInt once that overflows.
+trait Tally: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn is_zero(&self) -> bool;
+    // false on overflow
+    fn add_assign(&mut self, other: &Self) -> bool;
+    fn into_big(self) -> BigInt;
+}
+
+impl Tally for u128 {
+    fn zero() -> u128 {
+        0
+    }
+
+    fn one() -> u128 {
+        1
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn add_assign(&mut self, other: &u128) -> bool {
+        match self.checked_add(*other) {
+            Some(sum) => {
+                *self = sum;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn into_big(self) -> BigInt {
+        BigInt::from(self)
+    }
+}
+
+impl Tally for BigInt {
+    fn zero() -> BigInt {
+        BigInt::zero()
+    }
+
+    fn one() -> BigInt {
+        BigInt::one()
+    }
+
+    fn is_zero(&self) -> bool {
+        BigInt::is_zero(self)
+    }
+
+    fn add_assign(&mut self, other: &BigInt) -> bool {
+        *self = &*self + other;
+        true
+    }
+
+    fn into_big(self) -> BigInt {
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Paths {
+    Finite(BigInt),
+    // some matching path runs through a cycle, so it can loop forever
+    Infinite,
+}
+
+impl fmt::Display for Paths {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Paths::Finite(n) => write!(f, "{}", n),
+            Paths::Infinite => write!(f, "infinite"),
+        }
+    }
+}
+
+// Exact number of matching paths. `cyclic` lists the condensed devices that
+// hide a cycle; when `infinite` is set a path through one of them counts as
+// infinitely many paths, otherwise paths are counted in the condensation.
+fn count_paths(graph: &Graph, topo: &[u32], query: &Query, cyclic: &[u32], infinite: bool) -> Paths {
+    let exact = |query: &Query| match count_paths_in::<u128>(graph, topo, query) {
+        Some(n) => n.into_big(),
+        None => count_paths_in::<BigInt>(graph, topo, query).unwrap(),
+    };
+    let total = exact(query);
+    if infinite && !cyclic.is_empty() && !total.is_zero() {
+        let mut acyclic = query.clone();
+        acyclic.forbidden.extend_from_slice(cyclic);
+        if exact(&acyclic) != total {
+            return Paths::Infinite;
+        }
+    }
+    Paths::Finite(total)
+}
+
+// Counts matching paths by pushing counts forward in topological order. Each
+// device carries one counter per subset of the required devices seen so far,
+// indexed by bitmask. None if T overflows.
+fn count_paths_in<T: Tally>(graph: &Graph, topo: &[u32], query: &Query) -> Option<T> {
+    let required = query.all_required();
+    assert!(required.len() <= MAX_REQUIRED, "at most {} required devices", MAX_REQUIRED);
+    let states = 1usize << required.len();
+    let full = states - 1;
+
+    let mut bit = vec![0usize; graph.len()];
+    for (i, &v) in required.iter().enumerate() {
+        bit[v as usize] |= 1 << i;
+    }
+    // devices whose bits must already be set when a path enters this one;
+    // after condensing, devices in the same component can come in any order
+    let mut needs = vec![0usize; graph.len()];
+    for pair in query.order.windows(2) {
+        needs[pair[1] as usize] |= bit[pair[0] as usize] & !bit[pair[1] as usize];
+    }
+    let mut blocked = vec![false; graph.len()];
+    for &v in &query.forbidden {
+        blocked[v as usize] = true;
+    }
+
+    let source = query.source as usize;
+    if blocked[source] || needs[source] != 0 {
+        return Some(T::zero());
+    }
+    let mut ways = vec![T::zero(); graph.len() * states];
+    ways[source * states + bit[source]] = T::one();
+    let mut here = vec![T::zero(); states];
+    for &u in topo {
+        let row = u as usize * states;
+        if u == query.target || ways[row..row + states].iter().all(T::is_zero) {
             continue;
         }
+        // a device's counts are final once reached in topological order;
+        // moving them out keeps only the frontier alive, which matters once
+        // the counts are big integers
+        for (h, w) in here.iter_mut().zip(&mut ways[row..row + states]) {
+            *h = std::mem::replace(w, T::zero());
+        }
+        for &v in graph.outputs(u) {
+            let v = v as usize;
+            if blocked[v] {
+                continue;
+            }
+            for (seen, count) in here.iter().enumerate() {
+                if !count.is_zero() && seen & needs[v] == needs[v] && !ways[v * states + (seen | bit[v])].add_assign(count) {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(ways[query.target as usize * states + full].clone())
+}
+
+fn part1_count_paths(graph: &Graph, topo: &[u32], cyclic: &[u32], infinite: bool) -> Paths {
+    let Ok(query) = Query::new(graph, "you", "out") else {
+        return Paths::Finite(BigInt::zero());
+    };
+    return count_paths(graph, topo, &query, cyclic, infinite);
+}
+
+fn part2_count_paths_with_dac_fft(graph: &Graph, topo: &[u32], cyclic: &[u32], infinite: bool) -> Paths {
+    let Ok(mut query) = Query::new(graph, "svr", "out") else {
+        return Paths::Finite(BigInt::zero());
+    };
+    let (Some(dac), Some(fft)) = (graph.id("dac"), graph.id("fft")) else {
+        return Paths::Finite(BigInt::zero());
+    };
+    query.required = vec![dac, fft];
+    return count_paths(graph, topo, &query, cyclic, infinite);
+}
+
+fn parse_input(input: &str) -> Graph {
+    let mut names: Vec<String> = Vec::new();
+    let mut ids: HashMap<String, u32> = HashMap::new();
+    let mut intern = |name: &str| -> u32 {
+        if let Some(&id) = ids.get(name) {
+            return id;
+        }
+        let id = names.len() as u32;
+        names.push(name.to_string());
+        ids.insert(name.to_string(), id);
+        id
+    };
 
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() != 2 {
+    let mut edges: Vec<(u32, u32)> = Vec::new();
+    for line in input.lines() {
+        if line.trim().is_empty() {
             continue;
         }
 
-        let node = parts[0].trim().to_string();
-        let neighbors: Vec<String> = parts[1]
-            .trim()
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
+        let Some((node, outputs)) = line.split_once(':') else {
+            continue;
+        };
 
-        graph.insert(node, neighbors);
+        let u = intern(node.trim());
+        for name in outputs.split_whitespace() {
+            edges.push((u, intern(name)));
+        }
     }
 
-    return graph;
+    return Graph::from_edges(names, ids, &edges);
+}
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(String::as_str)
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -73,15 +447,57 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let t1 = Instant::now();
     let input = fs::read_to_string("inputs/day11_in.txt")?;
     let graph = parse_input(&input);
-    let part1 = part1_count_paths(&graph);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // a cyclic graph is an error unless --cycles says how to count: in the
+    // condensation ("condense"), or as infinite when a path can loop
+    let cycles = arg_value(&args, "--cycles");
+    let (graph, order, cyclic) = match topological_order(&graph) {
+        Ok(order) => (graph, order, Vec::new()),
+        Err(chain) => {
+            let chain = describe_chain(&graph, &chain);
+            match cycles {
+                None => return Err(format!("device graph has a cycle: {} (see --cycles)", chain).into()),
+                Some("condense") | Some("infinite") => {
+                    println!("Cycle found: {}", chain);
+                    let (condensed, cyclic) = condense(&graph);
+                    let order = topological_order(&condensed).expect("condensation is acyclic");
+                    (condensed, order, cyclic)
+                }
+                Some(other) => return Err(format!("unknown --cycles mode '{}'", other).into()),
+            }
+        }
+    };
+    let infinite = cycles == Some("infinite");
+
+    // a custom question instead of the two parts, e.g.
+    //   --from svr --to out --require dac,fft --forbid abc --order dac,fft
+    // where --order lists devices that must be met in that sequence
+    let flags = ["--from", "--to", "--require", "--forbid", "--order"];
+    if flags.iter().any(|f| args.iter().any(|a| a == f)) {
+        let source = arg_value(&args, "--from").unwrap_or("you");
+        let target = arg_value(&args, "--to").unwrap_or("out");
+        let mut query = Query::new(&graph, source, target)?;
+        query.required = devices(&graph, arg_value(&args, "--require").unwrap_or(""))?;
+        query.forbidden = devices(&graph, arg_value(&args, "--forbid").unwrap_or(""))?;
+        query.order = devices(&graph, arg_value(&args, "--order").unwrap_or(""))?;
+        if query.all_required().len() > MAX_REQUIRED {
+            return Err(format!("at most {} required devices", MAX_REQUIRED).into());
+        }
+        let paths = count_paths(&graph, &order, &query, &cyclic, infinite);
+        println!("Paths from '{}' to '{}': {} (elapsed: {:?})", source, target, paths, t1.elapsed());
+        return Ok(());
+    }
+
+    let part1 = part1_count_paths(&graph, &order, &cyclic, infinite);
     let d1 = t1.elapsed();
     println!("Part 1: Paths from 'you' to 'out': {} (elapsed: {:?})", part1, d1);
 
     // Part 2
     let t2 = Instant::now();
-    let part2 = part2_count_paths_with_dac_fft(&graph);
+    let part2 = part2_count_paths_with_dac_fft(&graph, &order, &cyclic, infinite);
     let d2 = t2.elapsed();
     println!("Part 2: Paths from 'svr' to 'out' visiting both 'dac' and 'fft': {} (elapsed: {:?})", part2, d2);
 
     Ok(())
-}
\ No newline at end of file
+}
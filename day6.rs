@@ -1,14 +1,109 @@
+mod bigint;
+
+use bigint::BigInt;
+use std::fmt;
 use std::fs;
 use std::time::Instant;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Max,
+    Min,
+    Pow,
+}
+
+impl Op {
+    fn parse(token: &str) -> Option<Op> {
+        match token {
+            "+" => Some(Op::Add),
+            "-" => Some(Op::Sub),
+            "*" => Some(Op::Mul),
+            "/" => Some(Op::Div),
+            "%" => Some(Op::Rem),
+            "max" => Some(Op::Max),
+            "min" => Some(Op::Min),
+            "^" => Some(Op::Pow),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Rem => "%",
+            Op::Max => "max",
+            Op::Min => "min",
+            Op::Pow => "^",
+        }
+    }
+
+    // value of a problem with no numbers, matching the old sum/product behaviour
+    fn empty_value(self) -> u64 {
+        if self == Op::Mul { 1 } else { 0 }
+    }
+}
+
 struct Input {
     numbers: Vec<Vec<u64>>,
-    operators: Vec<char>
+    operators: Vec<Op>,
+    // half-open worksheet column range of each problem block
+    columns: Vec<(usize, usize)>
 }
 
-fn part1_parse_input(input: &str) -> Input {
+#[derive(Debug)]
+// `problem` is the 0-based block index; messages number problems from 1
+enum SolveError {
+    Overflow { problem: usize, op: Op },
+    DivisionByZero { problem: usize, op: Op },
+    ExponentTooLarge { problem: usize },
+    ResultTooLarge { problem: usize },
+    TotalOverflow,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveError::Overflow { problem, op } => write!(
+                f, "problem {} ({}) overflowed u64, rerun with --big", problem + 1, op.symbol()
+            ),
+            SolveError::DivisionByZero { problem, op } => write!(
+                f, "problem {} ({}) divides by zero", problem + 1, op.symbol()
+            ),
+            SolveError::ExponentTooLarge { problem } => write!(
+                f, "problem {} (^) has an exponent that does not fit in u32", problem + 1
+            ),
+            SolveError::ResultTooLarge { problem } => write!(
+                f, "problem {} (^) would exceed {} bits", problem + 1, MAX_BIG_BITS
+            ),
+            SolveError::TotalOverflow => write!(f, "grand total overflowed u64, rerun with --big"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+fn is_operator_row(line: &str) -> bool {
+    line.split_whitespace().next().and_then(Op::parse).is_some()
+}
+
+fn parse_operators(line: &str) -> Result<Vec<Op>, String> {
+    line.split_whitespace()
+        .map(|s| Op::parse(s).ok_or_else(|| format!("unknown operator '{}'", s)))
+        .collect()
+}
+
+fn part1_parse_input(input: &str) -> Result<Input, String> {
     let mut numbers = Vec::with_capacity(4);
     let mut operators = Vec::new();
+    let mut lines_vec: Vec<Vec<char>> = Vec::with_capacity(4);
 
     for line in input.lines() {
         let line = line.trim();
@@ -16,28 +111,34 @@ fn part1_parse_input(input: &str) -> Input {
             continue;
         }
 
-        if let Some(first_char) = line.chars().next() {
-            if first_char == '*' || first_char == '+' {
-                operators = line.as_bytes()
-                    .iter()
-                    .filter(|&&b| b == b'*' || b == b'+')
-                    .map(|&b| b as char)
-                    .collect();
-            } else if first_char.is_ascii_digit() {
+        if is_operator_row(line) {
+            operators = parse_operators(line)?;
+        } else if let Some(first_char) = line.chars().next() {
+            if first_char.is_ascii_digit() {
                 let row: Vec<u64> = line.split_whitespace()
                     .filter_map(|s| s.parse().ok())
                     .collect();
                 numbers.push(row);
+                lines_vec.push(line.chars().collect());
             }
         }
     }
 
+    if let Some(row) = numbers.iter().position(|r| r.len() != operators.len()) {
+        return Err(format!(
+            "number row {} has {} numbers but there are {} operators", row + 1, numbers[row].len(), operators.len()
+        ));
+    }
     let transposed = transpose(numbers);
+    let columns = find_blocks(&lines_vec)
+        .into_iter()
+        .filter(|&(start, end)| (start..end).any(|col| column_has_digit(&lines_vec, col)))
+        .collect();
 
-    Input { numbers: transposed, operators }
+    Ok(Input { numbers: transposed, operators, columns })
 }
 
-fn part2_parse_input(input: &str) -> Input {
+fn part2_parse_input(input: &str) -> Result<Input, String> {
     let mut lines_vec: Vec<Vec<char>> = Vec::new();
     let mut operators = Vec::new();
 
@@ -47,41 +148,17 @@ fn part2_parse_input(input: &str) -> Input {
             continue;
         }
 
-        if line.contains('*') || line.contains('+') {
-            operators = line.split_whitespace()
-                .map(|s| s.chars().next().unwrap())
-                .collect();
+        if is_operator_row(line) {
+            operators = parse_operators(line)?;
         } else {
             lines_vec.push(line.chars().collect());
         }
     }
 
-    // find columns where ALL rows have a space
-    let max_len = lines_vec.iter().map(|l| l.len()).max().unwrap_or(0);
-    let mut separator_positions = Vec::with_capacity(operators.len() + 1);
-    separator_positions.push(0);
-
-    for col in 0..max_len {
-        let all_space = lines_vec.iter().all(|line| {
-            col >= line.len() || line[col] == ' '
-        });
-
-        if all_space {
-            if let Some(&last_sep) = separator_positions.last() {
-                if col > last_sep {
-                    separator_positions.push(col);
-                }
-            }
-        }
-    }
-    separator_positions.push(max_len);
-
     // extract numbers from each equation block
     let mut numbers = Vec::with_capacity(operators.len());
-    for i in 0..separator_positions.len() - 1 {
-        let start = separator_positions[i];
-        let end = separator_positions[i + 1];
-
+    let mut columns = Vec::with_capacity(operators.len());
+    for (start, end) in find_blocks(&lines_vec) {
         let mut equation_numbers = Vec::new();
 
         for col in start..end {
@@ -107,10 +184,48 @@ fn part2_parse_input(input: &str) -> Input {
 
         if !equation_numbers.is_empty() {
             numbers.push(equation_numbers);
+            columns.push((start, end));
+        }
+    }
+
+    if numbers.len() != operators.len() {
+        return Err(format!("{} number blocks but {} operators", numbers.len(), operators.len()));
+    }
+
+    Ok(Input { numbers, operators, columns })
+}
+
+fn column_has_digit(lines_vec: &[Vec<char>], col: usize) -> bool {
+    lines_vec.iter().any(|line| line.get(col).is_some_and(|c| c.is_ascii_digit()))
+}
+
+// split the worksheet into problem blocks at columns where ALL rows have a
+// space; the separator column itself is not part of either block
+fn find_blocks(lines_vec: &[Vec<char>]) -> Vec<(usize, usize)> {
+    let max_len = lines_vec.iter().map(|l| l.len()).max().unwrap_or(0);
+    let mut separator_positions = Vec::new();
+    separator_positions.push(0);
+
+    for col in 0..max_len {
+        let all_space = lines_vec.iter().all(|line| {
+            col >= line.len() || line[col] == ' '
+        });
+
+        if all_space {
+            if let Some(&last_sep) = separator_positions.last() {
+                if col > last_sep {
+                    separator_positions.push(col);
+                }
+            }
         }
     }
+    separator_positions.push(max_len);
 
-    Input { numbers, operators }
+    separator_positions.windows(2)
+        .enumerate()
+        .map(|(i, w)| (if i == 0 { w[0] } else { w[0] + 1 }, w[1]))
+        .filter(|&(start, end)| start < end)
+        .collect()
 }
 
 fn transpose(matrix: Vec<Vec<u64>>) -> Vec<Vec<u64>> {
@@ -126,35 +241,252 @@ fn transpose(matrix: Vec<Vec<u64>>) -> Vec<Vec<u64>> {
     }).collect()
 }
 
+fn apply(op: Op, a: u64, b: u64) -> Option<u64> {
+    match op {
+        Op::Add => a.checked_add(b),
+        Op::Sub => a.checked_sub(b),
+        Op::Mul => a.checked_mul(b),
+        Op::Div => a.checked_div(b),
+        Op::Rem => a.checked_rem(b),
+        Op::Max => Some(a.max(b)),
+        Op::Min => Some(a.min(b)),
+        Op::Pow => match a {
+            0 | 1 => Some(if b == 0 { 1 } else { a }),
+            _ => u32::try_from(b).ok().and_then(|e| a.checked_pow(e)),
+        },
+    }
+}
+
+// results of ^ in --big mode are capped so a huge exponent fails instead of
+// trying to allocate gigabytes
+const MAX_BIG_BITS: u64 = 1 << 24;
+
+// division by zero is ruled out by the caller
+fn apply_big(problem: usize, op: Op, a: &BigInt, b: &BigInt) -> Result<BigInt, SolveError> {
+    match op {
+        Op::Add => Ok(a + b),
+        Op::Sub => Ok(a - b),
+        Op::Mul => Ok(a * b),
+        Op::Div => Ok(a.div_rem(b).unwrap().0),
+        Op::Rem => Ok(a.div_rem(b).unwrap().1),
+        Op::Max => Ok(a.max(b).clone()),
+        Op::Min => Ok(a.min(b).clone()),
+        Op::Pow => {
+            if a.is_zero() || *a == BigInt::one() {
+                return Ok(if b.is_zero() { BigInt::one() } else { a.clone() });
+            }
+            let e = b.to_u64().and_then(|e| u32::try_from(e).ok())
+                .ok_or(SolveError::ExponentTooLarge { problem })?;
+            a.checked_pow(e, MAX_BIG_BITS).ok_or(SolveError::ResultTooLarge { problem })
+        }
+    }
+}
+
+// each problem is folded left to right in reading order, so `-`, `/`, `%` and
+// `^` are left-associative: a - b - c == (a - b) - c
+fn solve_problem(problem: usize, op: Op, nums: &[u64]) -> Result<u64, SolveError> {
+    let Some((&first, rest)) = nums.split_first() else {
+        return Ok(op.empty_value());
+    };
+    rest.iter().try_fold(first, |acc, &n| {
+        if n == 0 && (op == Op::Div || op == Op::Rem) {
+            return Err(SolveError::DivisionByZero { problem, op });
+        }
+        apply(op, acc, n).ok_or(SolveError::Overflow { problem, op })
+    })
+}
+
+fn solve_problem_big(problem: usize, op: Op, nums: &[u64]) -> Result<BigInt, SolveError> {
+    let Some((&first, rest)) = nums.split_first() else {
+        return Ok(BigInt::from(op.empty_value()));
+    };
+    rest.iter().try_fold(BigInt::from(first), |acc, &n| {
+        if n == 0 && (op == Op::Div || op == Op::Rem) {
+            return Err(SolveError::DivisionByZero { problem, op });
+        }
+        apply_big(problem, op, &acc, &BigInt::from(n))
+    })
+}
+
 #[inline]
-fn solve(parsed: &Input) -> u64 {
-    parsed.operators.iter().enumerate().map(|(i, &op)| {
-        if op == '+' {
-            parsed.numbers[i].iter().sum::<u64>()
+fn solve(parsed: &Input) -> Result<u64, SolveError> {
+    parsed.operators.iter().enumerate().try_fold(0u64, |total, (i, &op)| {
+        let value = solve_problem(i, op, &parsed.numbers[i])?;
+        total.checked_add(value).ok_or(SolveError::TotalOverflow)
+    })
+}
+
+fn solve_big(parsed: &Input) -> Result<BigInt, SolveError> {
+    parsed.operators.iter().enumerate()
+        .map(|(i, &op)| solve_problem_big(i, op, &parsed.numbers[i]))
+        .sum()
+}
+
+// software reference for day6_hw_part2.ml (and day6_part2.py): the raw input
+// sits in one byte memory and every read is row_offsets[row] + col + curr_off.
+// Registers are 64 bits wide, so accumulation wraps like the hardware does,
+// and reads past the end of memory return 0.
+const HW_DIGIT_ROWS: usize = 4;
+
+fn part2_memory(mem: &[u8]) -> u64 {
+    let read = |addr: usize| mem.get(addr).copied().unwrap_or(0);
+
+    let row_len = mem.iter().position(|&b| b == b'\n').unwrap_or(mem.len());
+    let row_offsets: Vec<usize> = (0..=HW_DIGIT_ROWS).map(|r| r * (row_len + 1)).collect();
+
+    let mut grand_total: u64 = 0;
+    for col in 0..row_len.saturating_sub(1) {
+        let operator = read(row_offsets[HW_DIGIT_ROWS] + col);
+        if operator != b'*' && operator != b'+' {
+            continue;
+        }
+        let is_mult = operator == b'*';
+
+        let mut column_result: u64 = if is_mult { 1 } else { 0 };
+        let mut curr_off = 0;
+        loop {
+            let mut current_num: u64 = 0;
+            for &row_offset in &row_offsets[..HW_DIGIT_ROWS] {
+                let digit = read(row_offset + col + curr_off);
+                if digit.is_ascii_digit() {
+                    current_num = current_num.wrapping_mul(10).wrapping_add((digit - b'0') as u64);
+                }
+            }
+            if current_num == 0 {
+                break;
+            }
+            column_result = if is_mult {
+                column_result.wrapping_mul(current_num)
+            } else {
+                column_result.wrapping_add(current_num)
+            };
+            curr_off += 1;
+        }
+        grand_total = grand_total.wrapping_add(column_result);
+    }
+    grand_total
+}
+
+// the memory engine only knows the hardware's + and * on exactly
+// HW_DIGIT_ROWS digit rows of equal width, with an operator row no wider
+// than them below (reads past the end of a row would land in the next one)
+fn hw_layout(input: &str, parsed: &Input) -> Result<(), String> {
+    let rows: Vec<&str> = input.lines().collect();
+    if rows.len() != HW_DIGIT_ROWS + 1 || !is_operator_row(rows[HW_DIGIT_ROWS].trim()) {
+        return Err(format!("worksheet is not {} digit rows and an operator row", HW_DIGIT_ROWS));
+    }
+    if rows[..HW_DIGIT_ROWS].iter().any(|row| row.len() != rows[0].len()) {
+        return Err("digit rows differ in length".to_string());
+    }
+    if rows[HW_DIGIT_ROWS].len() > rows[0].len() {
+        return Err("operator row is longer than the digit rows".to_string());
+    }
+    if !parsed.operators.iter().all(|&op| op == Op::Add || op == Op::Mul) {
+        return Err("worksheet uses operators other than + and *".to_string());
+    }
+    if !parsed.numbers.iter().all(|nums| nums.iter().all(|&n| n < 10_000)) {
+        return Err("numbers wider than four digits".to_string());
+    }
+    Ok(())
+}
+
+const BLOCK_COLORS: [&str; 6] = ["\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m"];
+const RESET: &str = "\x1b[0m";
+
+fn explain(label: &str, parsed: &Input, big: bool) {
+    println!("{} breakdown:", label);
+    for (i, &op) in parsed.operators.iter().enumerate() {
+        let nums = parsed.numbers.get(i).map(Vec::as_slice).unwrap_or(&[]);
+        let (start, end) = parsed.columns.get(i).copied().unwrap_or((0, 0));
+        let result = if big {
+            solve_problem_big(i, op, nums).map(|v| v.to_string())
         } else {
-            parsed.numbers[i].iter().product::<u64>()
+            solve_problem(i, op, nums).map(|v| v.to_string())
+        };
+        let result = result.unwrap_or_else(|e| format!("error: {}", e));
+        let nums: Vec<String> = nums.iter().map(u64::to_string).collect();
+        println!(
+            "  #{:<4} cols {:>5}..{:<5} {:>3}  {} = {}",
+            i + 1, start, end, op.symbol(), nums.join(" "), result
+        );
+    }
+}
+
+// the worksheet with every problem block in its own colour, so the columns a
+// reading mode assigned to each problem can be checked by eye
+fn render_worksheet(input: &str, columns: &[(usize, usize)]) -> String {
+    let mut out = String::new();
+    for line in input.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let mut current = None;
+        for (col, ch) in line.chars().enumerate() {
+            let idx = columns.partition_point(|&(_, end)| end <= col);
+            let block = columns.get(idx).filter(|&&(start, _)| start <= col).map(|_| idx);
+            if block != current {
+                out.push_str(block.map_or(RESET, |b| BLOCK_COLORS[b % BLOCK_COLORS.len()]));
+                current = block;
+            }
+            out.push(ch);
         }
-    }).sum()
+        out.push_str(RESET);
+        out.push('\n');
+    }
+    out
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // --big computes every problem exactly instead of with checked u64
+    // --explain[=1|2] prints each problem block, --color also draws the worksheet
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let big = args.iter().any(|a| a == "--big");
+    let color = args.iter().any(|a| a == "--color");
+    let explain_parts: &[u8] = match args.iter().find(|a| a.starts_with("--explain")).map(String::as_str) {
+        Some("--explain=1") => &[1],
+        Some("--explain=2") => &[2],
+        Some(_) => &[1, 2],
+        None => &[],
+    };
+
     // Part 1
     let start_time = Instant::now();
     let input = fs::read_to_string("inputs/day6_in.txt")?;
-    let parsed = part1_parse_input(&input);
-    let sum1 = solve(&parsed);
+    let parsed = part1_parse_input(&input)?;
+    // map to String so main reports the Display message, not the Debug form;
+    // errors are only raised after --explain has shown the breakdown
+    let sum1 = if big { solve_big(&parsed).map(|v| v.to_string()) } else { solve(&parsed).map(|v| v.to_string()) };
+    let sum1 = sum1.map_err(|e| e.to_string());
     let duration1 = start_time.elapsed();
 
     // Part 2
     let start_time = Instant::now();
-    let parsed2 = part2_parse_input(&input);
-    let sum2 = solve(&parsed2);
+    let parsed2 = part2_parse_input(&input)?;
+    let sum2 = if big { solve_big(&parsed2).map(|v| v.to_string()) } else { solve(&parsed2).map(|v| v.to_string()) };
+    let sum2 = sum2.map_err(|e| e.to_string());
     let duration2 = start_time.elapsed();
 
+    let start_time = Instant::now();
+    let sum2_mem = hw_layout(&input, &parsed2).map(|()| part2_memory(input.as_bytes()));
+    let duration2_mem = start_time.elapsed();
+
+    for &part in explain_parts {
+        let parsed = if part == 1 { &parsed } else { &parsed2 };
+        if color {
+            print!("{}", render_worksheet(&input, &parsed.columns));
+        }
+        explain(&format!("Part {}", part), parsed, big);
+    }
+    let (sum1, sum2) = (sum1?, sum2?);
+
     println!("Part 1 sum:  {}", sum1);
     println!("Part 1 time: {:?}", duration1);
     println!("Part 2 sum:  {}", sum2);
     println!("Part 2 time: {:?}", duration2);
+    match sum2_mem {
+        Ok(sum) => {
+            let verdict = if sum.to_string() == sum2 { "matches parser" } else { "MISMATCH with parser" };
+            println!("Part 2 memory engine: {} ({}, {:?})", sum, verdict, duration2_mem);
+        }
+        Err(reason) => println!("Part 2 memory engine: skipped ({})", reason),
+    }
 
     Ok(())
 }
\ No newline at end of file
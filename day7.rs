@@ -1,6 +1,6 @@
 use std::fs;
 use std::time::Instant;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 #[inline]
 fn part1(input: &str) -> u32 {
@@ -26,8 +26,38 @@ fn part1(input: &str) -> u32 {
     sum
 }
 
+// number of timelines currently in each column; a splitter moves every
+// timeline in its column to both neighbours, so one pass per row suffices
 #[inline]
-fn part2(input: &str) -> usize {
+fn part2(input: &str) -> u128 {
+    let mut lines = input.lines().step_by(2);
+    let first_line = lines.next().unwrap();
+
+    let mut timelines: Vec<u128> = first_line.bytes()
+        .map(|b| (b == b'S') as u128)
+        .collect();
+    let mut next = timelines.clone();
+
+    for line in lines {
+        next.copy_from_slice(&timelines);
+        for (i, &b) in line.as_bytes().iter().enumerate() {
+            if b == b'^' && timelines[i] > 0 {
+                let count = timelines[i];
+                next[i] -= count;
+                // no need for bounds checking
+                next[i - 1] += count;
+                next[i + 1] += count;
+            }
+        }
+        std::mem::swap(&mut timelines, &mut next);
+    }
+
+    timelines.iter().sum()
+}
+
+// enumerates every beam state explicitly; exponential in splitters hit per
+// row, so only used to check part2 on small manifolds
+fn part2_brute_force(input: &str) -> usize {
     let mut lines = input.lines().step_by(2);
     let first_line = lines.next().unwrap();
 
@@ -80,7 +110,260 @@ fn part2(input: &str) -> usize {
     current_states.values().sum()
 }
 
+// General manifold simulator. Unlike part1/part2 it looks at every row, allows
+// any number of `S` sources and beams that leave the grid, and understands a few
+// extra cells:
+//   ^   splits a vertical beam into beams beside it going the same way
+//   / \ mirrors, turning the beam 90 degrees
+//   #   absorbs the beam
+//   v   merge point, every beam reaching it continues downwards
+// Beams that meet in the same cell going the same way merge, so mirror loops
+// terminate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Dir {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Dir {
+    fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+
+    fn step(self, row: isize, col: isize) -> (isize, isize) {
+        match self {
+            Dir::Up => (row - 1, col),
+            Dir::Down => (row + 1, col),
+            Dir::Left => (row, col - 1),
+            Dir::Right => (row, col + 1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Exit {
+    Top(usize),
+    Bottom(usize),
+    Left(usize),
+    Right(usize),
+}
+
+struct Simulation {
+    splits: usize,
+    absorbed: usize,
+    exits: Vec<Exit>,
+}
+
+fn simulate(input: &str) -> Simulation {
+    let grid: Vec<&[u8]> = input.lines().map(str::as_bytes).collect();
+    let height = grid.len() as isize;
+    let width = grid.iter().map(|r| r.len()).max().unwrap_or(0) as isize;
+    let cell = |row: isize, col: isize| *grid[row as usize].get(col as usize).unwrap_or(&b'.');
+    let index = |row: isize, col: isize| (row * width + col) as usize;
+
+    let mut seen = vec![0u8; (height * width) as usize];
+    let mut fired = vec![false; (height * width) as usize];
+    let mut splits = 0;
+    let mut absorbed = 0;
+    let mut exits = BTreeSet::new();
+
+    let mut beams: Vec<(isize, isize, Dir)> = Vec::new();
+    for (row, line) in grid.iter().enumerate() {
+        for (col, &b) in line.iter().enumerate() {
+            if b == b'S' {
+                beams.push((row as isize, col as isize, Dir::Down));
+            }
+        }
+    }
+
+    while let Some((row, col, dir)) = beams.pop() {
+        if row < 0 {
+            exits.insert(Exit::Top(col as usize));
+            continue;
+        } else if row >= height {
+            exits.insert(Exit::Bottom(col as usize));
+            continue;
+        } else if col < 0 {
+            exits.insert(Exit::Left(row as usize));
+            continue;
+        } else if col >= width {
+            exits.insert(Exit::Right(row as usize));
+            continue;
+        }
+
+        let i = index(row, col);
+        if seen[i] & dir.bit() != 0 {
+            continue;
+        }
+        seen[i] |= dir.bit();
+
+        let next_dir = match (cell(row, col), dir) {
+            (b'^', Dir::Up | Dir::Down) => {
+                if !fired[i] {
+                    fired[i] = true;
+                    splits += 1;
+                }
+                beams.push((row, col - 1, dir));
+                beams.push((row, col + 1, dir));
+                continue;
+            }
+            (b'#', _) => {
+                absorbed += 1;
+                continue;
+            }
+            (b'/', Dir::Down) | (b'\\', Dir::Up) => Dir::Left,
+            (b'/', Dir::Up) | (b'\\', Dir::Down) => Dir::Right,
+            (b'/', Dir::Left) | (b'\\', Dir::Right) => Dir::Down,
+            (b'/', Dir::Right) | (b'\\', Dir::Left) => Dir::Up,
+            (b'v', _) => Dir::Down,
+            _ => dir,
+        };
+        let (next_row, next_col) = next_dir.step(row, col);
+        beams.push((next_row, next_col, next_dir));
+    }
+
+    Simulation { splits, absorbed, exits: exits.into_iter().collect() }
+}
+
+// Per-cell view of the puzzle manifold for rendering: for every row, which
+// columns carry a beam (part 1) and how many timelines (part 2) leave it going
+// down, plus which splitters fired. Beams leaving the sides are dropped.
+struct Trace {
+    grid: Vec<Vec<u8>>,
+    beams: Vec<Vec<bool>>,
+    timelines: Vec<Vec<u128>>,
+    fired: Vec<Vec<bool>>,
+}
+
+fn trace(input: &str) -> Trace {
+    let grid: Vec<Vec<u8>> = input.lines().map(|l| l.as_bytes().to_vec()).collect();
+    let width = grid.iter().map(|r| r.len()).max().unwrap_or(0);
+
+    let mut beams = Vec::with_capacity(grid.len());
+    let mut timelines = Vec::with_capacity(grid.len());
+    let mut fired = Vec::with_capacity(grid.len());
+    let mut cur_beams = vec![false; width];
+    let mut cur_timelines = vec![0u128; width];
+
+    for row in &grid {
+        let mut next_beams = cur_beams.clone();
+        let mut next_timelines = cur_timelines.clone();
+        let mut row_fired = vec![false; width];
+
+        for (i, &b) in row.iter().enumerate() {
+            if b == b'S' {
+                next_beams[i] = true;
+                next_timelines[i] += 1;
+            } else if b == b'^' && cur_beams[i] {
+                row_fired[i] = true;
+                let count = cur_timelines[i];
+                next_beams[i] = false;
+                next_timelines[i] -= count;
+                for j in [i.wrapping_sub(1), i + 1] {
+                    if j < width {
+                        next_beams[j] = true;
+                        next_timelines[j] += count;
+                    }
+                }
+            }
+        }
+
+        beams.push(next_beams.clone());
+        timelines.push(next_timelines.clone());
+        fired.push(row_fired);
+        cur_beams = next_beams;
+        cur_timelines = next_timelines;
+    }
+
+    Trace { grid, beams, timelines, fired }
+}
+
+// beams drawn as `|`, splitters that fired as `*`, idle splitters stay `^`
+fn render_beams(trace: &Trace) -> String {
+    let mut out = String::new();
+    for (r, row) in trace.grid.iter().enumerate() {
+        for (i, &b) in row.iter().enumerate() {
+            out.push(match b {
+                b'^' if trace.fired[r][i] => '*',
+                b'.' if trace.beams[r][i] => '|',
+                _ => b as char,
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// timeline counts span many orders of magnitude, so shade on a log scale
+fn heat(count: u128, max: u128) -> f64 {
+    if count == 0 || max <= 1 {
+        return if count == 0 { 0.0 } else { 1.0 };
+    }
+    ((count as f64).ln() + 1.0) / ((max as f64).ln() + 1.0)
+}
+
+fn render_heatmap(trace: &Trace) -> String {
+    const RAMP: &[u8] = b" .:-=+*#%@";
+    let max = trace.timelines.iter().flatten().copied().max().unwrap_or(0);
+
+    let mut out = String::new();
+    for (r, row) in trace.grid.iter().enumerate() {
+        for (i, &b) in row.iter().enumerate() {
+            if b == b'^' || b == b'S' {
+                out.push(b as char);
+                continue;
+            }
+            let level = (heat(trace.timelines[r][i], max) * (RAMP.len() - 1) as f64).round() as usize;
+            out.push(RAMP[level] as char);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn render_heatmap_svg(trace: &Trace) -> String {
+    const CELL: usize = 6;
+    let width = trace.grid.iter().map(|r| r.len()).max().unwrap_or(0);
+    let height = trace.grid.len();
+    let max = trace.timelines.iter().flatten().copied().max().unwrap_or(0);
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        width * CELL, height * CELL
+    );
+    out.push_str("<rect width=\"100%\" height=\"100%\" fill=\"black\"/>\n");
+    for (r, row) in trace.grid.iter().enumerate() {
+        for (i, &b) in row.iter().enumerate() {
+            let (x, y) = (i * CELL, r * CELL);
+            let count = trace.timelines[r][i];
+            if count > 0 {
+                // blue for a single timeline through red for the busiest cell
+                let t = heat(count, max);
+                let hue = 240.0 * (1.0 - t);
+                out.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"hsl({:.0},100%,50%)\"><title>{}</title></rect>\n",
+                    x, y, CELL, CELL, hue, count
+                ));
+            }
+            if b == b'^' {
+                let fill = if trace.fired[r][i] { "white" } else { "gray" };
+                out.push_str(&format!(
+                    "<polygon points=\"{},{} {},{} {},{}\" fill=\"{}\"/>\n",
+                    x + CELL / 2, y, x, y + CELL, x + CELL, y + CELL, fill
+                ));
+            }
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // --render draws the beams, --heatmap shades part 2 timeline counts,
+    // --svg <path> writes the heatmap as an SVG, --check runs the brute force
+    let args: Vec<String> = std::env::args().skip(1).collect();
     let input = fs::read_to_string("inputs/day7_in.txt")?;
 
     let start = Instant::now();
@@ -91,5 +374,44 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let sum2 = part2(&input);
     println!("Part 2: {} ({:?})", sum2, start.elapsed());
 
+    let start = Instant::now();
+    let sim = simulate(&input);
+    let bottom: Vec<String> = sim.exits.iter()
+        .filter_map(|e| match e {
+            Exit::Bottom(col) => Some(col.to_string()),
+            _ => None,
+        })
+        .collect();
+    println!(
+        "Simulator: {} splits, {} absorbed, {} exits ({:?})",
+        sim.splits, sim.absorbed, sim.exits.len(), start.elapsed()
+    );
+    println!("  bottom exit columns: {}", bottom.join(" "));
+    for exit in sim.exits.iter().filter(|e| !matches!(e, Exit::Bottom(_))) {
+        println!("  {:?}", exit);
+    }
+
+    if args.iter().any(|a| a == "--check") {
+        let start = Instant::now();
+        let oracle = part2_brute_force(&input) as u128;
+        let verdict = if oracle == sum2 { "matches" } else { "MISMATCH" };
+        println!("Part 2 brute force: {} ({}, {:?})", oracle, verdict, start.elapsed());
+    }
+
+    let svg_path = args.iter().position(|a| a == "--svg").and_then(|i| args.get(i + 1));
+    if args.iter().any(|a| a == "--render" || a == "--heatmap") || svg_path.is_some() {
+        let trace = trace(&input);
+        if args.iter().any(|a| a == "--render") {
+            print!("{}", render_beams(&trace));
+        }
+        if args.iter().any(|a| a == "--heatmap") {
+            print!("{}", render_heatmap(&trace));
+        }
+        if let Some(path) = svg_path {
+            fs::write(path, render_heatmap_svg(&trace))?;
+            println!("Heatmap written to {}", path);
+        }
+    }
+
     Ok(())
 }
\ No newline at end of file
@@ -1,43 +1,588 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
 use std::fs;
 use std::time::Instant;
 
-#[inline]
-fn parse_input(input: &str) -> Vec<(u32, u32, u32)> {
-    return input.lines()
-        .filter_map(|line| {
-            let mut parts = line.split(',');
-            Some((
-                parts.next()?.parse().ok()?,
-                parts.next()?.parse().ok()?,
-                parts.next()?.parse().ok()?
-            ))
-        })
-        .collect();
+// junction boxes as a flat array of `dim` coordinates per point
+struct Points {
+    dim: usize,
+    data: Vec<i64>,
 }
 
-#[inline]
-fn sq_dst(coord1: (u32, u32, u32), coord2: (u32, u32, u32)) -> i64 {
-    let dx = coord1.0 as i64 - coord2.0 as i64;
-    let dy = coord1.1 as i64 - coord2.1 as i64;
-    let dz = coord1.2 as i64 - coord2.2 as i64;
-    return dx * dx + dy * dy + dz * dz;
+impl Points {
+    #[inline]
+    fn len(&self) -> usize {
+        self.data.len() / self.dim
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> &[i64] {
+        &self.data[i * self.dim..(i + 1) * self.dim]
+    }
+}
+
+// any number of comma separated coordinates per line, the same on every line
+fn parse_input(input: &str) -> Result<Points, String> {
+    let mut dim = 0;
+    let mut data = Vec::new();
+
+    for (line_no, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let row = line.split(',')
+            .map(|part| part.trim().parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+        if dim == 0 {
+            dim = row.len();
+        } else if row.len() != dim {
+            return Err(format!("line {}: expected {} coordinates, found {}", line_no + 1, dim, row.len()));
+        }
+        data.extend(row);
+    }
+
+    Ok(Points { dim: dim.max(1), data })
+}
+
+// Distances only need to order pairs, so Euclidean stays squared and integer.
+#[derive(Clone, Debug)]
+enum Metric {
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+    // squared Euclidean with a weight per axis
+    Weighted(Vec<i64>),
+}
+
+impl Metric {
+    fn parse(s: &str, dim: usize) -> Result<Metric, String> {
+        match s {
+            "euclidean" => Ok(Metric::Euclidean),
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            _ => {
+                let weights = s.strip_prefix("weighted:")
+                    .ok_or_else(|| format!("unknown metric '{}'", s))?
+                    .split(',')
+                    .map(|w| w.trim().parse::<i64>().map_err(|e| format!("bad weight '{}': {}", w, e)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if weights.len() != dim {
+                    return Err(format!("{} weights given for {} dimensions", weights.len(), dim));
+                }
+                // the k-d tree prunes on per-axis lower bounds, which need
+                // every term to be non-negative
+                if let Some(w) = weights.iter().find(|&&w| w < 0) {
+                    return Err(format!("negative weight {}", w));
+                }
+                Ok(Metric::Weighted(weights))
+            }
+        }
+    }
+
+    // distances are plain i64, so make sure the widest pair of points still
+    // fits: in release builds an overflow would wrap and scramble the order
+    fn check_range(&self, points: &Points) -> Result<(), String> {
+        if points.len() == 0 {
+            return Ok(());
+        }
+        let mut widest: i64 = 0;
+        for axis in 0..points.dim {
+            let values = (0..points.len()).map(|i| points.get(i)[axis]);
+            let (lo, hi) = (values.clone().min().unwrap(), values.max().unwrap());
+            let gap = hi.checked_sub(lo);
+            let term = gap.and_then(|g| match self {
+                Metric::Euclidean => g.checked_mul(g),
+                Metric::Manhattan | Metric::Chebyshev => Some(g),
+                Metric::Weighted(w) => g.checked_mul(g).and_then(|sq| sq.checked_mul(w[axis])),
+            });
+            widest = match (self, term) {
+                (Metric::Chebyshev, Some(t)) => widest.max(t),
+                (_, Some(t)) => widest.checked_add(t).ok_or("distance sum overflows i64")?,
+                (_, None) => return Err(format!("coordinate gap on axis {} overflows i64 distances", axis)),
+            };
+        }
+        Ok(())
+    }
+
+    // contribution of a gap of `gap` along `axis`, folded together with combine
+    #[inline]
+    fn term(&self, axis: usize, gap: i64) -> i64 {
+        match self {
+            Metric::Euclidean => gap * gap,
+            Metric::Manhattan | Metric::Chebyshev => gap,
+            Metric::Weighted(w) => w[axis] * gap * gap,
+        }
+    }
+
+    #[inline]
+    fn combine(&self, acc: i64, term: i64) -> i64 {
+        match self {
+            Metric::Chebyshev => acc.max(term),
+            _ => acc + term,
+        }
+    }
+
+    #[inline]
+    fn dist(&self, a: &[i64], b: &[i64]) -> i64 {
+        a.iter().zip(b).enumerate()
+            .fold(0, |acc, (k, (x, y))| self.combine(acc, self.term(k, (x - y).abs())))
+    }
 }
 
 #[inline]
-fn compute_all_pairs(coords: &[(u32, u32, u32)]) -> Vec<(usize, usize, i64)> {
-    let n = coords.len();
-    let mut pairs = Vec::with_capacity((n * (n - 1)) / 2);
+fn compute_all_pairs(points: &Points, metric: &Metric) -> Vec<(usize, usize, i64)> {
+    let n = points.len();
+    let mut pairs = Vec::with_capacity((n * n.saturating_sub(1)) / 2);
 
     for i in 0..n {
         for j in (i + 1)..n {
-            pairs.push((i, j, sq_dst(coords[i], coords[j])));
+            pairs.push((i, j, metric.dist(points.get(i), points.get(j))));
         }
     }
 
-    pairs.sort_unstable_by_key(|&(_, _, dist)| dist);
+    pairs.sort_unstable_by_key(|&(i, j, dist)| (dist, i, j));
     return pairs;
 }
 
+// k-d tree stored implicitly: the node covering nodes[lo..hi] keeps its split
+// point at mid = (lo + hi) / 2. A node's coordinates and bounding box sit
+// together in `geom` so a visit stays within a few cache lines.
+struct KdNode {
+    point: usize,
+    split: usize,
+    // component shared by every point under the node, usize::MAX if mixed
+    label: usize,
+}
+
+struct KdTree<'a> {
+    points: &'a Points,
+    metric: &'a Metric,
+    nodes: Vec<KdNode>,
+    // per node: position, bounding box min, bounding box max (dim values each)
+    geom: Vec<i64>,
+}
+
+impl<'a> KdTree<'a> {
+    fn new(points: &'a Points, metric: &'a Metric) -> KdTree<'a> {
+        let n = points.len();
+        let mut order: Vec<usize> = (0..n).collect();
+        let mut tree = KdTree {
+            points,
+            metric,
+            nodes: Vec::with_capacity(n),
+            geom: vec![0; n * 3 * points.dim],
+        };
+        let mut splits = vec![0; n];
+        tree.build(&mut order, &mut splits, 0, n);
+
+        let dim = points.dim;
+        for (mid, (&point, &split)) in order.iter().zip(&splits).enumerate() {
+            tree.nodes.push(KdNode { point, split, label: usize::MAX });
+            tree.geom[mid * 3 * dim..mid * 3 * dim + dim].copy_from_slice(points.get(point));
+        }
+        tree
+    }
+
+    fn build(&mut self, order: &mut [usize], splits: &mut [usize], lo: usize, hi: usize) {
+        if lo >= hi {
+            return;
+        }
+        let dim = self.points.dim;
+        let mut min = vec![i64::MAX; dim];
+        let mut max = vec![i64::MIN; dim];
+        for &p in &order[lo..hi] {
+            for (a, &v) in self.points.get(p).iter().enumerate() {
+                min[a] = min[a].min(v);
+                max[a] = max[a].max(v);
+            }
+        }
+        let a = (0..dim).max_by_key(|&a| max[a] - min[a]).unwrap();
+        let mid = (lo + hi) / 2;
+        let points = self.points;
+        order[lo..hi].select_nth_unstable_by_key(mid - lo, |&p| points.get(p)[a]);
+        splits[mid] = a;
+        let base = mid * 3 * dim;
+        self.geom[base + dim..base + 2 * dim].copy_from_slice(&min);
+        self.geom[base + 2 * dim..base + 3 * dim].copy_from_slice(&max);
+        self.build(order, splits, lo, mid);
+        self.build(order, splits, mid + 1, hi);
+    }
+
+    #[inline]
+    fn pos(&self, mid: usize) -> &[i64] {
+        let dim = self.points.dim;
+        &self.geom[mid * 3 * dim..mid * 3 * dim + dim]
+    }
+
+    // smallest possible distance from q to anything under node mid
+    #[inline]
+    fn lower_bound(&self, q: &[i64], mid: usize) -> i64 {
+        let dim = self.points.dim;
+        let base = mid * 3 * dim;
+        let min = &self.geom[base + dim..base + 2 * dim];
+        let max = &self.geom[base + 2 * dim..base + 3 * dim];
+        (0..dim).fold(0, |acc, a| {
+            let gap = (min[a] - q[a]).max(q[a] - max[a]).max(0);
+            self.metric.combine(acc, self.metric.term(a, gap))
+        })
+    }
+
+    // children of node (lo, hi) with the one on q's side of the split first
+    #[inline]
+    fn children(&self, q: &[i64], lo: usize, hi: usize) -> [(usize, usize); 2] {
+        let mid = (lo + hi) / 2;
+        let a = self.nodes[mid].split;
+        if q[a] < self.pos(mid)[a] {
+            [(lo, mid), (mid + 1, hi)]
+        } else {
+            [(mid + 1, hi), (lo, mid)]
+        }
+    }
+
+    // the k nearest other points to q, ordered by (distance, index)
+    fn nearest(&self, q: usize, k: usize) -> Vec<(i64, usize)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        self.nearest_in(q, self.points.get(q), k, 0, self.nodes.len(), &mut heap);
+        heap.into_sorted_vec()
+    }
+
+    fn nearest_in(&self, q: usize, qpos: &[i64], k: usize, lo: usize, hi: usize, heap: &mut BinaryHeap<(i64, usize)>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        if heap.len() == k && self.lower_bound(qpos, mid) > heap.peek().unwrap().0 {
+            return;
+        }
+        let p = self.nodes[mid].point;
+        if p != q {
+            let cand = (self.metric.dist(qpos, self.pos(mid)), p);
+            if heap.len() < k {
+                heap.push(cand);
+            } else if cand < *heap.peek().unwrap() {
+                heap.pop();
+                heap.push(cand);
+            }
+        }
+        for (clo, chi) in self.children(qpos, lo, hi) {
+            self.nearest_in(q, qpos, k, clo, chi, heap);
+        }
+    }
+
+    fn label_components(&mut self, comp: &[usize], lo: usize, hi: usize) -> Option<usize> {
+        if lo >= hi {
+            return None;
+        }
+        let mid = (lo + hi) / 2;
+        let own = comp[self.nodes[mid].point];
+        let left = self.label_components(comp, lo, mid);
+        let right = self.label_components(comp, mid + 1, hi);
+        let same = left.is_none_or(|c| c == own) && right.is_none_or(|c| c == own);
+        self.nodes[mid].label = if same { own } else { usize::MAX };
+        Some(self.nodes[mid].label)
+    }
+
+    // shortest edge (distance, lower index, higher index) from q to a point in
+    // another component, improving on best if there is one
+    fn nearest_foreign(&self, q: usize, qpos: &[i64], comp: &[usize], lo: usize, hi: usize, best: &mut (i64, usize, usize)) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        if self.nodes[mid].label == comp[q] || self.lower_bound(qpos, mid) > best.0 {
+            return;
+        }
+        let p = self.nodes[mid].point;
+        if comp[p] != comp[q] {
+            let cand = (self.metric.dist(qpos, self.pos(mid)), q.min(p), q.max(p));
+            if cand < *best {
+                *best = cand;
+            }
+        }
+        for (clo, chi) in self.children(qpos, lo, hi) {
+            self.nearest_foreign(q, qpos, comp, clo, chi, best);
+        }
+    }
+}
+
+// Yields every pair in increasing (distance, i, j) order without materialising
+// them: each point has a buffered stream of its nearest neighbours, refilled
+// from the tree with a doubled k when it runs dry, and a heap merges the
+// streams. Every pair shows up in both endpoints' streams and is emitted once.
+struct NearestPairs<'a> {
+    tree: &'a KdTree<'a>,
+    fetched: Vec<usize>,
+    buffers: Vec<VecDeque<(i64, usize)>>,
+    heap: BinaryHeap<Reverse<(i64, usize, usize, usize)>>,
+}
+
+impl<'a> NearestPairs<'a> {
+    const INITIAL_K: usize = 8;
+
+    fn new(tree: &'a KdTree<'a>) -> NearestPairs<'a> {
+        let n = tree.points.len();
+        let mut pairs = NearestPairs {
+            tree,
+            fetched: vec![0; n],
+            buffers: vec![VecDeque::new(); n],
+            heap: BinaryHeap::with_capacity(n),
+        };
+        for i in 0..n {
+            pairs.advance(i, None);
+        }
+        pairs
+    }
+
+    // queue the next neighbour of i that comes after `last`
+    fn advance(&mut self, i: usize, last: Option<(i64, usize)>) {
+        let n = self.tree.points.len();
+        while self.buffers[i].is_empty() && self.fetched[i] < n - 1 {
+            let k = (self.fetched[i] * 2).max(Self::INITIAL_K).min(n - 1);
+            let prev = self.fetched[i];
+            self.buffers[i].extend(self.tree.nearest(i, k).into_iter().skip(prev));
+            self.fetched[i] = k;
+        }
+        while let Some(&(d, j)) = self.buffers[i].front() {
+            if last.is_some_and(|l| (d, j) <= l) {
+                self.buffers[i].pop_front();
+                continue;
+            }
+            self.heap.push(Reverse((d, i.min(j), i.max(j), i)));
+            return;
+        }
+    }
+}
+
+impl Iterator for NearestPairs<'_> {
+    type Item = (usize, usize, i64);
+
+    fn next(&mut self) -> Option<(usize, usize, i64)> {
+        while let Some(Reverse((d, lo, hi, owner))) = self.heap.pop() {
+            let other = if owner == lo { hi } else { lo };
+            self.buffers[owner].pop_front();
+            self.advance(owner, Some((d, other)));
+            if owner == lo {
+                return Some((lo, hi, d));
+            }
+        }
+        None
+    }
+}
+
+// Minimum spanning tree under the tree's metric by Boruvka rounds: every component takes its
+// shortest edge to another component, found with the tree while skipping
+// subtrees that lie entirely inside the component. Edges come back sorted.
+fn minimum_spanning_tree(tree: &mut KdTree) -> Vec<(usize, usize, i64)> {
+    let n = tree.points.len();
+    let mut dsu = DisjointSet::new(n);
+    let mut mst = Vec::with_capacity(n.saturating_sub(1));
+    let none = (i64::MAX, usize::MAX, usize::MAX);
+
+    while dsu.components > 1 {
+        let comp: Vec<usize> = (0..n).map(|i| dsu.find(i)).collect();
+        tree.label_components(&comp, 0, n);
+
+        let mut best = vec![none; n];
+        for q in 0..n {
+            tree.nearest_foreign(q, tree.points.get(q), &comp, 0, n, &mut best[comp[q]]);
+        }
+
+        for &(d, i, j) in best.iter().filter(|&&e| e != none) {
+            if dsu.union(i, j) {
+                mst.push((i, j, d));
+            }
+        }
+    }
+
+    mst.sort_unstable_by_key(|&(i, j, d)| (d, i, j));
+    mst
+}
+
+// union-find with path compression and union by size
+struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    components: usize,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> DisjointSet {
+        DisjointSet { parent: (0..n).collect(), size: vec![1; n], components: n }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        while self.parent[x] != root {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    // returns false if a and b were already in the same set
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        self.components -= 1;
+        true
+    }
+
+    fn component_sizes(&self) -> Vec<usize> {
+        (0..self.parent.len())
+            .filter(|&i| self.parent[i] == i)
+            .map(|i| self.size[i])
+            .collect()
+    }
+}
+
+#[inline]
+fn part1(pairs: impl IntoIterator<Item = (usize, usize, i64)>, n: usize, connections: usize, largest: usize) -> usize {
+    let mut dsu = DisjointSet::new(n);
+    for (i, j, _) in pairs.into_iter().take(connections) {
+        dsu.union(i, j);
+    }
+
+    let mut sizes = dsu.component_sizes();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+
+    return sizes.iter().take(largest).product();
+}
+
+// works on all pairs or just the MST edges, both sorted by distance
+#[inline]
+fn part2(pairs: impl IntoIterator<Item = (usize, usize, i64)>, points: &Points, n: usize) -> i64 {
+    let mut dsu = DisjointSet::new(n);
+    for (i, j, _) in pairs {
+        if dsu.union(i, j) && dsu.components == 1 {
+            return points.get(i)[0] * points.get(j)[0];
+        }
+    }
+
+    return 0;
+}
+
+// junction boxes of every circuit after the first `connections` pairs, largest
+// circuit first
+fn circuits(pairs: impl IntoIterator<Item = (usize, usize, i64)>, n: usize, connections: usize) -> Vec<Vec<usize>> {
+    let mut dsu = DisjointSet::new(n);
+    for (i, j, _) in pairs.into_iter().take(connections) {
+        dsu.union(i, j);
+    }
+
+    let mut members: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for i in 0..n {
+        let root = dsu.find(i);
+        members[root].push(i);
+    }
+    members.retain(|m| !m.is_empty());
+    members.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+    members
+}
+
+// one single-linkage merge: clusters below n are junction boxes, cluster
+// n + k is the one created by the k-th merge
+struct Merge {
+    left: usize,
+    right: usize,
+    dist: i64,
+    size: usize,
+}
+
+// Kruskal over the sorted MST edges gives single-linkage merge order
+fn dendrogram(mst: &[(usize, usize, i64)], n: usize) -> Vec<Merge> {
+    let mut dsu = DisjointSet::new(n);
+    let mut cluster: Vec<usize> = (0..n).collect();
+    let mut merges = Vec::with_capacity(mst.len());
+
+    for &(i, j, dist) in mst {
+        let (ri, rj) = (dsu.find(i), dsu.find(j));
+        let (left, right) = (cluster[ri], cluster[rj]);
+        dsu.union(i, j);
+        let root = dsu.find(i);
+        cluster[root] = n + merges.len();
+        merges.push(Merge { left, right, dist, size: dsu.size[root] });
+    }
+    merges
+}
+
+fn json_coords(points: &Points, i: usize) -> String {
+    let coords: Vec<String> = points.get(i).iter().map(i64::to_string).collect();
+    format!("[{}]", coords.join(","))
+}
+
+fn export_json(points: &Points, metric: &Metric, connections: usize, circuits: &[Vec<usize>],
+               mst: &[(usize, usize, i64)], merges: &[Merge]) -> String {
+    let squared = matches!(metric, Metric::Euclidean | Metric::Weighted(_));
+    let mut out = format!(
+        "{{\n  \"metric\": \"{:?}\",\n  \"squared_distances\": {},\n  \"connections\": {},\n  \"circuits\": [\n",
+        metric, squared, connections
+    );
+    let circuit_lines: Vec<String> = circuits.iter()
+        .map(|members| {
+            let boxes: Vec<String> = members.iter()
+                .map(|&i| format!("{{\"index\": {}, \"coords\": {}}}", i, json_coords(points, i)))
+                .collect();
+            format!("    {{\"size\": {}, \"members\": [{}]}}", members.len(), boxes.join(", "))
+        })
+        .collect();
+    out.push_str(&circuit_lines.join(",\n"));
+    out.push_str("\n  ],\n  \"mst\": [\n");
+    let edge_lines: Vec<String> = mst.iter()
+        .map(|&(i, j, d)| format!("    {{\"a\": {}, \"b\": {}, \"dist\": {}}}", i, j, d))
+        .collect();
+    out.push_str(&edge_lines.join(",\n"));
+    out.push_str("\n  ],\n  \"dendrogram\": [\n");
+    let merge_lines: Vec<String> = merges.iter().enumerate()
+        .map(|(k, m)| format!(
+            "    {{\"id\": {}, \"left\": {}, \"right\": {}, \"dist\": {}, \"size\": {}}}",
+            points.len() + k, m.left, m.right, m.dist, m.size
+        ))
+        .collect();
+    out.push_str(&merge_lines.join(",\n"));
+    out.push_str("\n  ]\n}\n");
+    out
+}
+
+// two graphs: the MST with each circuit as a cluster, and the dendrogram
+fn export_dot(points: &Points, circuits: &[Vec<usize>], mst: &[(usize, usize, i64)], merges: &[Merge]) -> String {
+    let mut out = String::from("graph mst {\n  node [shape=point];\n");
+    for (c, members) in circuits.iter().enumerate() {
+        out.push_str(&format!("  subgraph cluster_{} {{\n    label=\"circuit {} ({})\";\n", c, c, members.len()));
+        for &i in members {
+            out.push_str(&format!("    p{} [xlabel=\"{}\"];\n", i, json_coords(points, i)));
+        }
+        out.push_str("  }\n");
+    }
+    for &(i, j, d) in mst {
+        out.push_str(&format!("  p{} -- p{} [label=\"{}\"];\n", i, j, d));
+    }
+    out.push_str("}\n\ndigraph dendrogram {\n  node [shape=point];\n");
+    for (k, m) in merges.iter().enumerate() {
+        let id = points.len() + k;
+        out.push_str(&format!("  c{} [shape=box, label=\"{} ({})\"];\n", id, m.dist, m.size));
+        for child in [m.left, m.right] {
+            let name = if child < points.len() { format!("p{}", child) } else { format!("c{}", child) };
+            out.push_str(&format!("  c{} -> {};\n", id, name));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+// DFS-based versions, kept to check the union-find answers
 #[inline]
 fn find_components(graph: &[Vec<usize>]) -> Vec<usize> {
     let n = graph.len();
@@ -100,11 +645,10 @@ fn is_connected(graph: &[Vec<usize>], visited: &mut [bool], stack: &mut Vec<usiz
     return count == n;
 }
 
-#[inline]
-fn part1(pairs: &[(usize, usize, i64)], n: usize) -> usize {
+fn part1_dfs(pairs: &[(usize, usize, i64)], n: usize, connections: usize, largest: usize) -> usize {
     let mut graph = vec![Vec::with_capacity(10); n];
 
-    for &(i, j, _) in pairs.iter().take(1000) {
+    for &(i, j, _) in pairs.iter().take(connections) {
         graph[i].push(j);
         graph[j].push(i);
     }
@@ -112,11 +656,10 @@ fn part1(pairs: &[(usize, usize, i64)], n: usize) -> usize {
     let mut sizes = find_components(&graph);
     sizes.sort_unstable_by(|a, b| b.cmp(a));
 
-    return sizes.iter().take(3).product();
+    return sizes.iter().take(largest).product();
 }
 
-#[inline]
-fn part2(pairs: &[(usize, usize, i64)], coords: &[(u32, u32, u32)], n: usize) -> u64 {
+fn part2_dfs(pairs: &[(usize, usize, i64)], points: &Points, n: usize) -> i64 {
     let mut graph = vec![Vec::with_capacity(10); n];
     let mut visited = vec![false; n];
     let mut stack = Vec::with_capacity(n);
@@ -126,27 +669,75 @@ fn part2(pairs: &[(usize, usize, i64)], coords: &[(u32, u32, u32)], n: usize) ->
         graph[j].push(i);
 
         if is_connected(&graph, &mut visited, &mut stack) {
-            let x1 = coords[i].0 as u64;
-            let x2 = coords[j].0 as u64;
-            return x1 * x2;
+            return points.get(i)[0] * points.get(j)[0];
         }
     }
 
     return 0;
 }
 
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(String::as_str)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // --input <path>, --connections <n> (1000), --largest <k> (3),
+    // --metric euclidean|manhattan|chebyshev|weighted:<w1>,<w2>,... and --check;
+    // --circuits lists circuit members, --export json|dot [--out <path>] writes
+    // circuits, MST and dendrogram
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = arg_value(&args, "--input").unwrap_or("inputs/day8_in.txt");
+    let connections = arg_value(&args, "--connections").map_or(Ok(1000), str::parse)?;
+    let largest = arg_value(&args, "--largest").map_or(Ok(3), str::parse)?;
+
     let start = Instant::now();
-    let input = fs::read_to_string("inputs/day8_in.txt")?;
-    let coords = parse_input(&input);
-    let pairs = compute_all_pairs(&coords);
-    let n = coords.len();
-    let sum1 = part1(&pairs, n);
+    let input = fs::read_to_string(path)?;
+    let points = parse_input(&input)?;
+    let metric = Metric::parse(arg_value(&args, "--metric").unwrap_or("euclidean"), points.dim)?;
+    metric.check_range(&points)?;
+    let n = points.len();
+    let mut tree = KdTree::new(&points, &metric);
+    let sum1 = part1(NearestPairs::new(&tree), n, connections, largest);
     println!("Part 1: {} ({:?})", sum1, start.elapsed());
 
     let start = Instant::now();
-    let sum2 = part2(&pairs, &coords, n);
+    let mst = minimum_spanning_tree(&mut tree);
+    let sum2 = part2(mst.iter().copied(), &points, n);
     println!("Part 2: {} ({:?})", sum2, start.elapsed());
 
+    if args.iter().any(|a| a == "--circuits") || arg_value(&args, "--export").is_some() {
+        let circuits = circuits(NearestPairs::new(&tree), n, connections);
+        if args.iter().any(|a| a == "--circuits") {
+            for (c, members) in circuits.iter().enumerate() {
+                let boxes: Vec<String> = members.iter().map(|&i| json_coords(&points, i)).collect();
+                println!("Circuit {} ({} boxes): {}", c, members.len(), boxes.join(" "));
+            }
+        }
+        if let Some(format) = arg_value(&args, "--export") {
+            let merges = dendrogram(&mst, n);
+            let out = match format {
+                "json" => export_json(&points, &metric, connections, &circuits, &mst, &merges),
+                "dot" => export_dot(&points, &circuits, &mst, &merges),
+                _ => return Err(format!("unknown export format '{}'", format).into()),
+            };
+            match arg_value(&args, "--out") {
+                Some(out_path) => fs::write(out_path, out)?,
+                None => print!("{}", out),
+            }
+        }
+    }
+
+    // --check redoes both parts from every pair, with union-find and with DFS
+    if args.iter().any(|a| a == "--check") {
+        let start = Instant::now();
+        let pairs = compute_all_pairs(&points, &metric);
+        let all1 = part1(pairs.iter().copied(), n, connections, largest);
+        let all2 = part2(pairs.iter().copied(), &points, n);
+        let dfs1 = part1_dfs(&pairs, n, connections, largest);
+        let dfs2 = part2_dfs(&pairs, &points, n);
+        let verdict = if [all1, dfs1] == [sum1; 2] && [all2, dfs2] == [sum2; 2] { "matches" } else { "MISMATCH" };
+        println!("All-pairs check: {} {} / DFS {} {} ({}, {:?})", all1, all2, dfs1, dfs2, verdict, start.elapsed());
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,13 +1,182 @@
 use std::fs;
 use std::time::Instant;
 
-fn parse_input(input: &str) -> Vec<(i32, i32)> {
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+
+// red tiles in input order, with the input line each one came from
+struct RedTiles {
+    points: Vec<(i32, i32)>,
+    lines: Vec<usize>,
+}
+
+fn parse_input(input: &str) -> Result<RedTiles, String> {
     let mut points = Vec::new();
-    for line in input.lines() {
-        let (x, y) = line.split_once(',').unwrap();
-        points.push((x.parse().unwrap(), y.parse().unwrap()));
+    let mut lines = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = line.split_once(',')
+            .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)));
+        let Some(point) = parsed else {
+            return Err(format!("line {}: expected 'x,y', found '{}'", i + 1, line));
+        };
+        points.push(point);
+        lines.push(i + 1);
+    }
+    return Ok(RedTiles { points, lines });
+}
+
+// Problems found in the red tile loop. Edges are named by the input lines of
+// the two tiles they join.
+#[derive(Debug)]
+enum Issue {
+    TooFewTiles,
+    ZeroArea,
+    DuplicateTile { line: usize, first_line: usize },
+    NotAxisAligned { line: usize, next_line: usize },
+    Colinear { line: usize },
+    SelfIntersection { edge: (usize, usize), other: (usize, usize) },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::TooFewTiles => write!(f, "fewer than 4 red tiles, no loop possible"),
+            Issue::ZeroArea => write!(f, "the loop encloses no area"),
+            Issue::DuplicateTile { line, first_line } => {
+                write!(f, "line {}: same tile as line {}", line, first_line)
+            }
+            Issue::NotAxisAligned { line, next_line } => {
+                write!(f, "lines {} -> {}: tiles share neither a row nor a column", line, next_line)
+            }
+            Issue::Colinear { line } => write!(f, "line {}: tile sits in the middle of a straight run", line),
+            Issue::SelfIntersection { edge, other } => write!(
+                f, "edge {} -> {} touches edge {} -> {}", edge.0, edge.1, other.0, other.1
+            ),
+        }
+    }
+}
+
+struct Validation {
+    issues: Vec<Issue>,
+    // orientation with y growing downwards, as the tiles are laid out on screen
+    clockwise: bool,
+}
+
+fn signed_area2(points: &[(i32, i32)]) -> i128 {
+    let n = points.len();
+    (0..n).map(|i| {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        a.0 as i128 * b.1 as i128 - b.0 as i128 * a.1 as i128
+    }).sum()
+}
+
+// The pairwise self-intersection test is O(n^2), so it only runs when
+// `thorough` is set; the other checks are linear.
+fn validate(tiles: &RedTiles, thorough: bool) -> Validation {
+    let (points, lines) = (&tiles.points, &tiles.lines);
+    let n = points.len();
+    let mut issues = Vec::new();
+    if n < 4 {
+        issues.push(Issue::TooFewTiles);
+    }
+
+    let mut seen = HashMap::new();
+    for (&point, &line) in points.iter().zip(lines) {
+        if let Some(&first_line) = seen.get(&point) {
+            issues.push(Issue::DuplicateTile { line, first_line });
+        } else {
+            seen.insert(point, line);
+        }
+    }
+
+    let pt = |i: usize| (points[i % n].0 as i64, points[i % n].1 as i64);
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        if a.0 != b.0 && a.1 != b.1 {
+            issues.push(Issue::NotAxisAligned { line: lines[i], next_line: lines[(i + 1) % n] });
+        }
+    }
+
+    for (i, &line) in lines.iter().enumerate() {
+        let (a, b, c) = (pt(i + n - 1), pt(i), pt(i + 1));
+        let dot = (b.0 - a.0) * (c.0 - b.0) + (b.1 - a.1) * (c.1 - b.1);
+        if a != b && b != c && geometry::orient(a, b, c) == 0 && dot > 0 {
+            issues.push(Issue::Colinear { line });
+        }
+    }
+
+    // edges next to each other may only share their common tile; any other
+    // contact between edges makes the loop non-simple
+    for i in (0..n).filter(|_| thorough) {
+        for j in (i + 1)..n {
+            let (a1, a2, b1, b2) = (pt(i), pt(i + 1), pt(j), pt(j + 1));
+            let touches = if j == i + 1 || (i == 0 && j == n - 1) {
+                let (shared, p, q) = if j == i + 1 { (a2, a1, b2) } else { (a1, a2, b1) };
+                let back = (shared.0 - p.0) * (q.0 - shared.0) + (shared.1 - p.1) * (q.1 - shared.1);
+                shared != p && shared != q && geometry::orient(p, shared, q) == 0 && back < 0
+            } else {
+                geometry::crosses_properly(a1, a2, b1, b2)
+                    || geometry::on_segment(a1, a2, b1) || geometry::on_segment(a1, a2, b2)
+                    || geometry::on_segment(b1, b2, a1) || geometry::on_segment(b1, b2, a2)
+            };
+            if touches {
+                issues.push(Issue::SelfIntersection {
+                    edge: (lines[i], lines[(i + 1) % n]),
+                    other: (lines[j], lines[(j + 1) % n]),
+                });
+            }
+        }
+    }
+
+    let area2 = signed_area2(points);
+    if n >= 4 && area2 == 0 {
+        issues.push(Issue::ZeroArea);
+    }
+
+    Validation { issues, clockwise: area2 > 0 }
+}
+
+// drops repeated tiles and tiles in the middle of straight runs, and orders
+// the loop clockwise on screen. Dropped tiles still lie on the loop, so this
+// is only for the boundary: rectangle corners stay the input tiles
+fn normalize(tiles: &RedTiles) -> RedTiles {
+    let mut kept: Vec<usize> = Vec::with_capacity(tiles.points.len());
+    for i in 0..tiles.points.len() {
+        if kept.last().is_none_or(|&k| tiles.points[k] != tiles.points[i]) {
+            kept.push(i);
+        }
+    }
+    while kept.len() > 1 && tiles.points[kept[0]] == tiles.points[*kept.last().unwrap()] {
+        kept.pop();
+    }
+
+    // removing a tile can make its neighbour colinear, so repeat until stable
+    loop {
+        let n = kept.len();
+        let pt = |k: usize| (tiles.points[kept[k % n]].0 as i64, tiles.points[kept[k % n]].1 as i64);
+        let Some(drop) = (0..n).find(|&k| {
+            n > 3 && geometry::orient(pt(k + n - 1), pt(k), pt(k + 1)) == 0
+        }) else {
+            break;
+        };
+        kept.remove(drop);
+        kept.dedup_by(|a, b| tiles.points[*a] == tiles.points[*b]);
+    }
+
+    let mut result = RedTiles {
+        points: kept.iter().map(|&k| tiles.points[k]).collect(),
+        lines: kept.iter().map(|&k| tiles.lines[k]).collect(),
+    };
+    if signed_area2(&result.points) < 0 {
+        result.points.reverse();
+        result.lines.reverse();
     }
-    return points;
+    result
 }
 
 #[inline]
@@ -25,154 +194,534 @@ fn part1(points: &[(i32, i32)]) -> u64 {
     return max_area;
 }
 
-#[inline]
-fn part2(polygon: &[(i32, i32)]) -> u64 {
-    let mut max_area = 0;
+// Compressed grid: along each axis every distinct vertex coordinate gets a
+// one-tile-wide slot, and the gap up to the next coordinate one slot standing
+// for all the tiles in between (possibly none). The polygon only turns on
+// vertex coordinates, so each compressed cell is wholly inside or outside.
+struct CompressedGrid {
+    xs: Vec<i32>,
+    ys: Vec<i32>,
+    width: usize,
+    // prefix[r * (width + 1) + c]: outside cells holding tiles in slots [0, r) x [0, c)
+    prefix: Vec<u32>,
+}
 
-    let poly_min_x = polygon.iter().map(|p| p.0).min().unwrap();
-    let poly_max_x = polygon.iter().map(|p| p.0).max().unwrap();
-    let poly_min_y = polygon.iter().map(|p| p.1).min().unwrap();
-    let poly_max_y = polygon.iter().map(|p| p.1).max().unwrap();
+impl CompressedGrid {
+    // None if some edge is not axis-aligned. The corner tiles that will be
+    // queried get slots too, since a normalized polygon may not include them
+    fn new(polygon: &[(i32, i32)], corners: &[(i32, i32)]) -> Option<CompressedGrid> {
+        if polygon.is_empty() {
+            return None;
+        }
+        let mut xs: Vec<i32> = polygon.iter().chain(corners).map(|p| p.0).collect();
+        let mut ys: Vec<i32> = polygon.iter().chain(corners).map(|p| p.1).collect();
+        xs.sort_unstable();
+        xs.dedup();
+        ys.sort_unstable();
+        ys.dedup();
+        let width = 2 * xs.len() - 1;
+        let height = 2 * ys.len() - 1;
 
-    for i in 0..polygon.len() {
-        let pi = polygon[i];
-        for j in (i + 1)..polygon.len() {
-            let pj = polygon[j];
-            if pi.0 == pj.0 || pi.1 == pj.1 {
-                continue;
+        // 0 = not yet known, 1 = boundary, 2 = outside
+        let mut cells = vec![0u8; width * height];
+        for i in 0..polygon.len() {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            if a.0 != b.0 && a.1 != b.1 {
+                return None;
             }
+            let (ax, bx) = (Self::slot(&xs, a.0), Self::slot(&xs, b.0));
+            let (ay, by) = (Self::slot(&ys, a.1), Self::slot(&ys, b.1));
+            for y in ay.min(by)..=ay.max(by) {
+                for x in ax.min(bx)..=ax.max(bx) {
+                    cells[y * width + x] = 1;
+                }
+            }
+        }
 
-            let (x1, x2) = if pi.0 < pj.0 {
-                (pi.0, pj.0)
-            } else {
-                (pj.0, pi.0)
-            };
-            let (y1, y2) = if pi.1 < pj.1 {
-                (pi.1, pj.1)
-            } else {
-                (pj.1, pi.1)
-            };
+        // flood the outside in from every open cell on the border
+        let mut stack = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                if (y == 0 || x == 0 || y == height - 1 || x == width - 1) && cells[y * width + x] == 0 {
+                    cells[y * width + x] = 2;
+                    stack.push((x, y));
+                }
+            }
+        }
+        while let Some((x, y)) = stack.pop() {
+            let neighbors = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+            for (nx, ny) in neighbors {
+                if nx < width && ny < height && cells[ny * width + nx] == 0 {
+                    cells[ny * width + nx] = 2;
+                    stack.push((nx, ny));
+                }
+            }
+        }
 
-            if x1 < poly_min_x || x2 > poly_max_x || y1 < poly_min_y || y2 > poly_max_y {
-                continue;
+        // an odd slot is a gap, which may hold no tiles at all
+        let has_tiles = |coords: &[i32], slot: usize| slot.is_multiple_of(2) || coords[slot / 2 + 1] - coords[slot / 2] > 1;
+        let mut prefix = vec![0u32; (width + 1) * (height + 1)];
+        for y in 0..height {
+            for x in 0..width {
+                let bad = cells[y * width + x] == 2 && has_tiles(&xs, x) && has_tiles(&ys, y);
+                prefix[(y + 1) * (width + 1) + x + 1] = bad as u32
+                    + prefix[y * (width + 1) + x + 1]
+                    + prefix[(y + 1) * (width + 1) + x]
+                    - prefix[y * (width + 1) + x];
             }
+        }
 
-            let area = ((x2 - x1 + 1) as u64) * ((y2 - y1 + 1) as u64);
+        Some(CompressedGrid { xs, ys, width, prefix })
+    }
 
-            if area > max_area && is_rectangle_valid(polygon, x1, y1, x2, y2) {
-                let area = ((x2 - x1 + 1) as u64) * ((y2 - y1 + 1) as u64);
-                max_area = max_area.max(area);
+    #[inline]
+    fn slot(coords: &[i32], v: i32) -> usize {
+        2 * coords.binary_search(&v).unwrap()
+    }
+
+    // (x, y) slots of the corner tiles, looked up once so that rectangle
+    // checks need no searching
+    fn corner_slots(&self, corners: &[(i32, i32)]) -> Vec<(usize, usize)> {
+        corners.iter().map(|p| (Self::slot(&self.xs, p.0), Self::slot(&self.ys, p.1))).collect()
+    }
+
+    // whether every tile of the rectangle spanned by two corner slots is inside
+    #[inline]
+    fn is_rectangle_valid(&self, (ax, ay): (usize, usize), (bx, by): (usize, usize)) -> bool {
+        let (x1, x2) = (ax.min(bx), ax.max(bx) + 1);
+        let (y1, y2) = (ay.min(by), ay.max(by) + 1);
+        let w = self.width + 1;
+        self.prefix[y2 * w + x2] + self.prefix[y1 * w + x1] == self.prefix[y1 * w + x2] + self.prefix[y2 * w + x1]
+    }
+}
+
+// `corners` are the red tiles rectangles may use, `polygon` the loop they
+// must fit inside; they differ once the loop has been normalized
+#[inline]
+fn part2(polygon: &[(i32, i32)], corners: &[(i32, i32)]) -> u64 {
+    let Some(grid) = CompressedGrid::new(polygon, corners) else {
+        return part2_brute_force(polygon, corners);
+    };
+    let slots = grid.corner_slots(corners);
+
+    let mut max_area = 0;
+    for i in 0..corners.len() {
+        let pi = corners[i];
+        for j in i + 1..corners.len() {
+            let pj = corners[j];
+            let area = (pi.0.abs_diff(pj.0) as u64 + 1) * (pi.1.abs_diff(pj.1) as u64 + 1);
+            if area > max_area && grid.is_rectangle_valid(slots[i], slots[j]) {
+                max_area = area;
             }
         }
     }
-
     return max_area;
 }
 
+// tests every vertex pair against the polygon with exact geometry, O(n^3);
+// used for polygons that are not axis-aligned and to check part2
 #[inline]
-fn is_rectangle_valid(polygon: &[(i32, i32)], x1: i32, y1: i32, x2: i32, y2: i32) -> bool {
-    if !is_point_inside_polygon(polygon, x1, y1) || !is_point_inside_polygon(polygon, x1, y2) ||
-       !is_point_inside_polygon(polygon, x2, y1) || !is_point_inside_polygon(polygon, x2, y2) {
-        return false;
-    }
-
-    let rect_edges = [
-        ((x1, y1), (x2, y1)),
-        ((x2, y1), (x2, y2)),
-        ((x2, y2), (x1, y2)),
-        ((x1, y2), (x1, y1)),
-    ];
-
-    for i in 0..polygon.len() {
-        let j = (i + 1) % polygon.len();
-        let p1 = polygon[i];
-        let p2 = polygon[j];
-
-        let seg_min_x = p1.0.min(p2.0);
-        let seg_max_x = p1.0.max(p2.0);
-        let seg_min_y = p1.1.min(p2.1);
-        let seg_max_y = p1.1.max(p2.1);
-
-        if seg_max_x < x1 || seg_min_x > x2 || seg_max_y < y1 || seg_min_y > y2 {
-            continue;
+fn part2_brute_force(polygon: &[(i32, i32)], corners: &[(i32, i32)]) -> u64 {
+    let mut max_area = 0;
+    for i in 0..corners.len() {
+        let pi = corners[i];
+        for &pj in &corners[i + 1..] {
+            let area = (pi.0.abs_diff(pj.0) as u64 + 1) * (pi.1.abs_diff(pj.1) as u64 + 1);
+            if area > max_area && geometry::rectangle_inside(polygon, pi, pj) {
+                max_area = area;
+            }
         }
+    }
+    return max_area;
+}
 
-        for &(r1, r2) in &rect_edges {
-            if segments_intersect(p1, p2, r1, r2) {
-                return false;
-            }
+// Samples the polygon's bounding box `scale` times per unit and checks every
+// vertex pair against the samples. With scale 1 the samples are the tiles, so
+// it agrees with CompressedGrid; with scale 2 it also sees gaps narrower than
+// a tile, which is what geometry::rectangle_inside answers for rectilinear
+// polygons. Only feasible for small coordinate ranges.
+fn part2_rasterized(polygon: &[(i32, i32)], corners: &[(i32, i32)], scale: i64) -> u64 {
+    let min_x = polygon.iter().map(|p| p.0).min().unwrap() as i64;
+    let max_x = polygon.iter().map(|p| p.0).max().unwrap() as i64;
+    let min_y = polygon.iter().map(|p| p.1).min().unwrap() as i64;
+    let max_y = polygon.iter().map(|p| p.1).max().unwrap() as i64;
+    let width = ((max_x - min_x) * scale + 1) as usize;
+    let height = ((max_y - min_y) * scale + 1) as usize;
+
+    // prefix sums of samples outside the polygon
+    let scaled: Vec<(i64, i64)> = polygon.iter().map(|p| (p.0 as i64 * scale, p.1 as i64 * scale)).collect();
+    let mut prefix = vec![0u32; (width + 1) * (height + 1)];
+    for y in 0..height {
+        for x in 0..width {
+            let sample = (min_x * scale + x as i64, min_y * scale + y as i64);
+            let bad = geometry::locate(&scaled, sample) == geometry::Location::Outside;
+            prefix[(y + 1) * (width + 1) + x + 1] = bad as u32
+                + prefix[y * (width + 1) + x + 1]
+                + prefix[(y + 1) * (width + 1) + x]
+                - prefix[y * (width + 1) + x];
         }
     }
 
-    return true;
+    let slot = |v: i32, min: i64| ((v as i64 - min) * scale) as usize;
+    let mut max_area = 0;
+    for i in 0..corners.len() {
+        let pi = corners[i];
+        for &pj in &corners[i + 1..] {
+            let (x1, x2) = (slot(pi.0.min(pj.0), min_x), slot(pi.0.max(pj.0), min_x) + 1);
+            let (y1, y2) = (slot(pi.1.min(pj.1), min_y), slot(pi.1.max(pj.1), min_y) + 1);
+            let w = width + 1;
+            let outside = prefix[y2 * w + x2] + prefix[y1 * w + x1] - prefix[y1 * w + x2] - prefix[y2 * w + x1];
+            if outside == 0 {
+                let area = (pi.0.abs_diff(pj.0) as u64 + 1) * (pi.1.abs_diff(pj.1) as u64 + 1);
+                max_area = max_area.max(area);
+            }
+        }
+    }
+    max_area
 }
 
-#[inline]
-fn segments_intersect(p1: (i32, i32), p2: (i32, i32), q1: (i32, i32), q2: (i32, i32)) -> bool {
-    let d = (p1.0 - p2.0) as i64 * (q1.1 - q2.1) as i64
-        - (p1.1 - p2.1) as i64 * (q1.0 - q2.0) as i64;
+// Exact integer geometry for the polygon through the red tiles. Cross
+// products are taken in i128, so no input coordinates can overflow them.
+//
+// Polygons and rectangles are closed sets: a rectangle is inside when every
+// point of it is inside the polygon or on its boundary, so rectangles running
+// along the boundary are allowed. This is the continuous reading; it only
+// differs from counting tiles when the polygon leaves an outside gap narrower
+// than one tile.
+mod geometry {
+    pub type Point = (i64, i64);
 
-    if d == 0 {
-        return false;
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum Location {
+        Inside,
+        Boundary,
+        Outside,
     }
 
-    let t = ((p1.0 - q1.0) as i64 * (q1.1 - q2.1) as i64
-        - (p1.1 - q1.1) as i64 * (q1.0 - q2.0) as i64) as f64
-        / d as f64;
-    let u = -((p1.0 - p2.0) as i64 * (p1.1 - q1.1) as i64
-        - (p1.1 - p2.1) as i64 * (p1.0 - q1.0) as i64) as f64
-        / d as f64;
+    // sign of the turn a -> b -> c: 1 counter-clockwise, -1 clockwise, 0 collinear
+    #[inline]
+    pub fn orient(a: Point, b: Point, c: Point) -> i32 {
+        let cross = (b.0 - a.0) as i128 * (c.1 - a.1) as i128 - (b.1 - a.1) as i128 * (c.0 - a.0) as i128;
+        cross.signum() as i32
+    }
+
+    #[inline]
+    pub fn on_segment(a: Point, b: Point, p: Point) -> bool {
+        orient(a, b, p) == 0
+            && p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0)
+            && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1)
+    }
+
+    // the segments cross at a single point interior to both; touching at an
+    // endpoint or overlapping collinearly does not count
+    #[inline]
+    pub fn crosses_properly(p1: Point, p2: Point, q1: Point, q2: Point) -> bool {
+        orient(p1, p2, q1) * orient(p1, p2, q2) < 0 && orient(q1, q2, p1) * orient(q1, q2, p2) < 0
+    }
 
-    return t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0;
+    pub fn locate(polygon: &[Point], p: Point) -> Location {
+        let n = polygon.len();
+        let mut inside = false;
+        for i in 0..n {
+            let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+            if on_segment(a, b, p) {
+                return Location::Boundary;
+            }
+            // half-open rule on y so a vertex is counted once; p is left of
+            // an upward edge exactly when the turn a -> b -> p is counter-clockwise
+            if (a.1 > p.1) != (b.1 > p.1) && (orient(a, b, p) > 0) == (b.1 > a.1) {
+                inside = !inside;
+            }
+        }
+        if inside { Location::Inside } else { Location::Outside }
+    }
+
+    // whether the closed segment a-b meets the open rectangle (x1, x2) x (y1, y2)
+    fn segment_meets_open_rect(a: Point, b: Point, x1: i64, y1: i64, x2: i64, y2: i64) -> bool {
+        if a.0.max(b.0) <= x1 || a.0.min(b.0) >= x2 || a.1.max(b.1) <= y1 || a.1.min(b.1) >= y2 {
+            return false;
+        }
+        let sides = [(x1, y1), (x2, y1), (x2, y2), (x1, y2)].map(|c| orient(a, b, c));
+        !(sides.iter().all(|&s| s >= 0) || sides.iter().all(|&s| s <= 0))
+    }
+
+    // whether the closed rectangle with opposite corners c1 and c2 lies within
+    // the closed polygon
+    pub fn rectangle_inside(polygon: &[(i32, i32)], c1: (i32, i32), c2: (i32, i32)) -> bool {
+        // doubled coordinates keep rectangle midpoints on integers
+        let poly: Vec<Point> = polygon.iter().map(|p| (2 * p.0 as i64, 2 * p.1 as i64)).collect();
+        let (x1, x2) = (2 * c1.0.min(c2.0) as i64, 2 * c1.0.max(c2.0) as i64);
+        let (y1, y2) = (2 * c1.1.min(c2.1) as i64, 2 * c1.1.max(c2.1) as i64);
+
+        if [(x1, y1), (x2, y1), (x2, y2), (x1, y2)].iter().any(|&c| locate(&poly, c) == Location::Outside) {
+            return false;
+        }
+
+        let n = poly.len();
+        if x1 < x2 && y1 < y2 {
+            // any boundary point in the open rectangle puts outside points
+            // next to it; otherwise the interior is all in or all out
+            for i in 0..n {
+                if segment_meets_open_rect(poly[i], poly[(i + 1) % n], x1, y1, x2, y2) {
+                    return false;
+                }
+            }
+            return locate(&poly, ((x1 + x2) / 2, (y1 + y2) / 2)) != Location::Outside;
+        }
+
+        // degenerate rectangle: a segment (or a point) from a to b. Nothing may
+        // cross it, and each piece between polygon vertices lying on it must
+        // have its midpoint inside.
+        let (a, b) = ((x1, y1), (x2, y2));
+        if (0..n).any(|i| crosses_properly(a, b, poly[i], poly[(i + 1) % n])) {
+            return false;
+        }
+        let mut stops: Vec<Point> = poly.iter().copied().filter(|&v| on_segment(a, b, v)).collect();
+        stops.push(a);
+        stops.push(b);
+        stops.sort_unstable();
+        stops.dedup();
+        stops.windows(2).all(|w| {
+            let mid = ((w[0].0 + w[1].0) / 2, (w[0].1 + w[1].1) / 2);
+            locate(&poly, mid) != Location::Outside
+        })
+    }
+}
+
+// rectangle with the red tiles at indices a < b as opposite corners
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Rectangle {
+    area: u64,
+    a: usize,
+    b: usize,
 }
 
 #[inline]
-fn is_point_inside_polygon(polygon: &[(i32, i32)], px: i32, py: i32) -> bool {
-    for i in 0..polygon.len() {
-        let j = (i + 1) % polygon.len();
-        if is_on_segment(polygon[i], polygon[j], px, py) {
-            return true;
+fn rect_area(p: (i32, i32), q: (i32, i32)) -> u64 {
+    (p.0.abs_diff(q.0) as u64 + 1) * (p.1.abs_diff(q.1) as u64 + 1)
+}
+
+// The k largest rectangles whose corners pass `valid`, largest first (ties by
+// corner indices). Every rectangle tied with the k-th is included as well, so
+// more than k may come back. `valid` takes the two tile indices.
+fn largest_rectangles(points: &[(i32, i32)], k: usize, valid: impl Fn(usize, usize) -> bool) -> Vec<Rectangle> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut areas: BinaryHeap<Reverse<u64>> = BinaryHeap::with_capacity(k + 1);
+    let mut kept = Vec::new();
+    for a in 0..points.len() {
+        for b in (a + 1)..points.len() {
+            let area = rect_area(points[a], points[b]);
+            if areas.len() == k && area < areas.peek().unwrap().0 {
+                continue;
+            }
+            if valid(a, b) {
+                kept.push(Rectangle { area, a, b });
+                areas.push(Reverse(area));
+                if areas.len() > k {
+                    areas.pop();
+                }
+            }
         }
     }
+    let threshold = areas.peek().map_or(0, |r| r.0);
+    kept.retain(|r| r.area >= threshold);
+    kept.sort_unstable_by_key(|r| (Reverse(r.area), r.a, r.b));
+    kept
+}
 
-    let mut inside = false;
-    for i in 0..polygon.len() {
-        let j = (i + 1) % polygon.len();
-        let (xi, yi) = polygon[i];
-        let (xj, yj) = polygon[j];
+// the largest valid rectangle with red tile `corner` as one of its corners
+fn largest_with_corner(points: &[(i32, i32)], corner: usize, valid: impl Fn(usize, usize) -> bool) -> Option<Rectangle> {
+    (0..points.len())
+        .filter(|&other| other != corner)
+        .map(|other| Rectangle { area: rect_area(points[corner], points[other]), a: corner.min(other), b: corner.max(other) })
+        .filter(|r| valid(r.a, r.b))
+        .max_by_key(|r| (r.area, Reverse(r.a), Reverse(r.b)))
+}
 
-        if ((yi > py) != (yj > py)) && (px < (xj - xi) * (py - yi) / (yj - yi) + xi) {
-            inside = !inside;
+// the largest valid rectangle covering the tile at `tile`, red or not
+fn largest_containing(points: &[(i32, i32)], tile: (i32, i32), valid: impl Fn(usize, usize) -> bool) -> Option<Rectangle> {
+    let covers = |p: (i32, i32), q: (i32, i32)| {
+        tile.0 >= p.0.min(q.0) && tile.0 <= p.0.max(q.0) && tile.1 >= p.1.min(q.1) && tile.1 <= p.1.max(q.1)
+    };
+    let mut best: Option<Rectangle> = None;
+    for a in 0..points.len() {
+        for b in (a + 1)..points.len() {
+            let area = rect_area(points[a], points[b]);
+            if best.is_some_and(|r| area <= r.area) || !covers(points[a], points[b]) {
+                continue;
+            }
+            if valid(a, b) {
+                best = Some(Rectangle { area, a, b });
+            }
         }
     }
-    return inside;
+    best
 }
 
-#[inline]
-fn is_on_segment(p1: (i32, i32), p2: (i32, i32), px: i32, py: i32) -> bool {
-    let min_x = p1.0.min(p2.0);
-    let max_x = p1.0.max(p2.0);
-    let min_y = p1.1.min(p2.1);
-    let max_y = p1.1.max(p2.1);
+fn describe(points: &[(i32, i32)], r: &Rectangle) -> String {
+    format!("{} (tiles #{} {:?} and #{} {:?})", r.area, r.a, points[r.a], r.b, points[r.b])
+}
+
+// Draws the loop of red tiles, the best part 1 rectangle (blue, dashed), the
+// best part 2 rectangle (orange) and any further part 2 candidates (grey),
+// scaled so the longer side is 1000px. Rectangles cover whole tiles, so they
+// reach half a tile past their corner tiles' centres.
+fn render_svg(points: &[(i32, i32)], part1: Option<Rectangle>, part2: &[Rectangle]) -> String {
+    const SIZE: f64 = 1000.0;
+    const MARGIN: f64 = 10.0;
+    let min_x = points.iter().map(|p| p.0).min().unwrap_or(0) as f64 - 0.5;
+    let max_x = points.iter().map(|p| p.0).max().unwrap_or(0) as f64 + 0.5;
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0) as f64 - 0.5;
+    let max_y = points.iter().map(|p| p.1).max().unwrap_or(0) as f64 + 0.5;
+    let scale = SIZE / (max_x - min_x).max(max_y - min_y);
+    let sx = |x: f64| MARGIN + (x - min_x) * scale;
+    let sy = |y: f64| MARGIN + (y - min_y) * scale;
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\">\n",
+        (max_x - min_x) * scale + 2.0 * MARGIN, (max_y - min_y) * scale + 2.0 * MARGIN
+    );
+    let outline: Vec<String> = points.iter()
+        .map(|p| format!("{:.2},{:.2}", sx(p.0 as f64), sy(p.1 as f64)))
+        .collect();
+    out.push_str(&format!(
+        "<polygon points=\"{}\" fill=\"#c8f0c8\" stroke=\"#2e8b57\" stroke-width=\"1\"/>\n",
+        outline.join(" ")
+    ));
+    for p in points {
+        out.push_str(&format!(
+            "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"1.5\" fill=\"red\"/>\n", sx(p.0 as f64), sy(p.1 as f64)
+        ));
+    }
 
-    if px < min_x || px > max_x || py < min_y || py > max_y {
-        return false;
+    let rect = |r: &Rectangle, style: &str| {
+        let (p, q) = (points[r.a], points[r.b]);
+        let (x1, x2) = (p.0.min(q.0) as f64 - 0.5, p.0.max(q.0) as f64 + 0.5);
+        let (y1, y2) = (p.1.min(q.1) as f64 - 0.5, p.1.max(q.1) as f64 + 0.5);
+        format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {}><title>{} ({:?} - {:?})</title></rect>\n",
+            sx(x1), sy(y1), (x2 - x1) * scale, (y2 - y1) * scale, style, r.area, p, q
+        )
+    };
+    for r in part2.iter().skip(1).rev() {
+        out.push_str(&rect(r, "fill=\"none\" stroke=\"#888888\" stroke-width=\"1\""));
     }
+    if let Some(r) = part1 {
+        out.push_str(&rect(&r, "fill=\"none\" stroke=\"#1f4fd8\" stroke-width=\"2\" stroke-dasharray=\"8 4\""));
+    }
+    if let Some(r) = part2.first() {
+        out.push_str(&rect(r, "fill=\"#ff8c00\" fill-opacity=\"0.35\" stroke=\"#ff8c00\" stroke-width=\"2\""));
+    }
+    out.push_str("</svg>\n");
+    out
+}
 
-    return ((py - p1.1) as i64 * (p2.0 - p1.0) as i64
-            - (px - p1.0) as i64 * (p2.1 - p1.1) as i64) == 0;
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(String::as_str)
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let start = Instant::now();
+    // --validate lists every problem with the red tile loop, --normalize
+    // cleans it up before solving, --check cross-checks part 2, and
+    // --svg <path> draws the loop with the best rectangles. Queries over the
+    // part 2 rectangles: --top <k> lists the k largest, --corner <tile index>
+    // and --contains <x>,<y> find the largest using or covering a tile.
+    let args: Vec<String> = std::env::args().skip(1).collect();
     let input = fs::read_to_string("inputs/day9_in.txt")?;
-    let points = parse_input(&input);
+    let tiles = parse_input(&input)?;
+
+    let full_validation = args.iter().any(|a| a == "--validate");
+    let validation = validate(&tiles, full_validation);
+    if full_validation {
+        println!("Loop runs {}", if validation.clockwise { "clockwise" } else { "counter-clockwise" });
+        for issue in &validation.issues {
+            println!("  {}", issue);
+        }
+    } else if !validation.issues.is_empty() {
+        println!("Warning: {} problems with the red tiles, rerun with --validate", validation.issues.len());
+    }
+    // normalizing only changes the loop rectangles must fit in; corners and
+    // tile indices keep referring to the input tiles
+    let polygon = if args.iter().any(|a| a == "--normalize") {
+        let normalized = normalize(&tiles);
+        println!("Normalized loop: {} -> {} red tiles", tiles.points.len(), normalized.points.len());
+        normalized.points
+    } else {
+        tiles.points.clone()
+    };
+    let points = tiles.points;
+    let start = Instant::now();
     let part1_res = part1(&points);
     println!("Part 1: {} ({:?})", part1_res, start.elapsed());
 
     let start = Instant::now();
-    let part2_res = part2(&points);
+    let part2_res = part2(&polygon, &points);
     println!("Part 2: {} ({:?})", part2_res, start.elapsed());
 
+    if args.iter().any(|a| a == "--check") {
+        let start = Instant::now();
+        let brute = part2_brute_force(&polygon, &points);
+        let verdict = if brute == part2_res { "matches" } else { "differs from" };
+        println!("Part 2 exact geometry: {} ({} tile count, {:?})", brute, verdict, start.elapsed());
+
+        // rasterizing is only practical on small coordinate ranges
+        let span = |f: fn(&(i32, i32)) -> i32| {
+            points.iter().map(f).max().unwrap() as i64 - points.iter().map(f).min().unwrap() as i64 + 1
+        };
+        if span(|p| p.0) * span(|p| p.1) <= 250_000 {
+            let tiles = part2_rasterized(&polygon, &points, 1);
+            let halves = part2_rasterized(&polygon, &points, 2);
+            let verdict = |a: u64, b: u64| if a == b { "matches" } else { "MISMATCH" };
+            println!("Rasterized by tile: {} ({} tile count)", tiles, verdict(tiles, part2_res));
+            println!("Rasterized by half tile: {} ({} exact geometry)", halves, verdict(halves, brute));
+        }
+    }
+
+    // the queries build their own grid; skip it when none was asked for
+    if !["--top", "--corner", "--contains", "--svg"].iter().any(|&flag| arg_value(&args, flag).is_some()) {
+        return Ok(());
+    }
+    let grid = CompressedGrid::new(&polygon, &points);
+    let slots = grid.as_ref().map(|grid| grid.corner_slots(&points));
+    let valid = |a: usize, b: usize| match (&grid, &slots) {
+        (Some(grid), Some(slots)) => grid.is_rectangle_valid(slots[a], slots[b]),
+        _ => geometry::rectangle_inside(&polygon, points[a], points[b]),
+    };
+    let top = arg_value(&args, "--top").map(str::parse::<usize>).transpose()?;
+
+    if let Some(k) = top {
+        println!("Largest {} rectangles:", k);
+        for r in largest_rectangles(&points, k, valid) {
+            println!("  {}", describe(&points, &r));
+        }
+    }
+    if let Some(corner) = arg_value(&args, "--corner") {
+        let corner: usize = corner.parse()?;
+        if corner >= points.len() {
+            return Err(format!("tile index {} out of range, {} red tiles", corner, points.len()).into());
+        }
+        match largest_with_corner(&points, corner, valid) {
+            Some(r) => println!("Largest with corner #{}: {}", corner, describe(&points, &r)),
+            None => println!("No valid rectangle has tile #{} as a corner", corner),
+        }
+    }
+    if let Some(tile) = arg_value(&args, "--contains") {
+        let (x, y) = tile.split_once(',').ok_or("expected --contains <x>,<y>")?;
+        let tile = (x.trim().parse()?, y.trim().parse()?);
+        match largest_containing(&points, tile, valid) {
+            Some(r) => println!("Largest containing {:?}: {}", tile, describe(&points, &r)),
+            None => println!("No valid rectangle contains {:?}", tile),
+        }
+    }
+
+    if let Some(path) = arg_value(&args, "--svg") {
+        let best1 = largest_rectangles(&points, 1, |_, _| true).first().copied();
+        let best2 = largest_rectangles(&points, top.unwrap_or(1), valid);
+        fs::write(path, render_svg(&points, best1, &best2))?;
+        println!("SVG written to {}", path);
+    }
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,423 @@
+#![allow(dead_code)]
+
+// Exact linear algebra over the rationals for integer matrices. Elimination is
+// fraction-free (Bareiss), so every intermediate entry is a minor of the input
+// and divisions are exact. It runs in checked i128 first and redoes the work
+// with BigInt if anything overflows. Needs `mod bigint;` next to `mod linalg;`.
+
+use crate::bigint::BigInt;
+use std::cmp::Ordering;
+use std::fmt;
+
+// rational in lowest terms with a positive denominator
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl Rational {
+    pub fn new(num: BigInt, den: BigInt) -> Rational {
+        assert!(!den.is_zero(), "zero denominator");
+        let g = num.gcd(&den);
+        let (mut num, mut den) = if g.is_zero() {
+            (num, den)
+        } else {
+            (num.div_rem(&g).unwrap().0, den.div_rem(&g).unwrap().0)
+        };
+        if den.is_negative() {
+            num = -num;
+            den = -den;
+        }
+        Rational { num, den }
+    }
+
+    pub fn zero() -> Rational {
+        Rational::from(0)
+    }
+
+    pub fn numer(&self) -> &BigInt {
+        &self.num
+    }
+
+    pub fn denom(&self) -> &BigInt {
+        &self.den
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.num.is_negative()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den == BigInt::one()
+    }
+
+    // largest integer <= self
+    pub fn floor(&self) -> BigInt {
+        let (q, r) = self.num.div_rem(&self.den).unwrap();
+        if r.is_negative() { q - BigInt::one() } else { q }
+    }
+
+    // smallest integer >= self
+    pub fn ceil(&self) -> BigInt {
+        -Rational::new(-self.num.clone(), self.den.clone()).floor()
+    }
+
+    pub fn neg(&self) -> Rational {
+        Rational { num: -self.num.clone(), den: self.den.clone() }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.is_integer() { self.num.to_i64() } else { None }
+    }
+
+    pub fn add(&self, o: &Rational) -> Rational {
+        Rational::new(&self.num * &o.den + &o.num * &self.den, &self.den * &o.den)
+    }
+
+    pub fn sub(&self, o: &Rational) -> Rational {
+        Rational::new(&self.num * &o.den - &o.num * &self.den, &self.den * &o.den)
+    }
+
+    pub fn mul(&self, o: &Rational) -> Rational {
+        Rational::new(&self.num * &o.num, &self.den * &o.den)
+    }
+
+    // None on division by zero
+    pub fn div(&self, o: &Rational) -> Option<Rational> {
+        if o.is_zero() {
+            return None;
+        }
+        Some(Rational::new(&self.num * &o.den, &self.den * &o.num))
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(v: i64) -> Rational {
+        Rational { num: BigInt::from(v), den: BigInt::one() }
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, o: &Rational) -> Ordering {
+        (&self.num * &o.den).cmp(&(&o.num * &self.den))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, o: &Rational) -> Option<Ordering> {
+        Some(self.cmp(o))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+// the integer arithmetic Bareiss needs; None means "overflowed, retry wider"
+trait Ring: Clone {
+    fn from_i64(v: i64) -> Self;
+    fn is_zero(&self) -> bool;
+    // (a * b - c * d) / e, exact
+    fn cross(a: &Self, b: &Self, c: &Self, d: &Self, e: &Self) -> Option<Self>;
+    fn to_big(&self) -> BigInt;
+}
+
+impl Ring for i128 {
+    fn from_i64(v: i64) -> i128 {
+        v as i128
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn cross(a: &i128, b: &i128, c: &i128, d: &i128, e: &i128) -> Option<i128> {
+        let v = a.checked_mul(*b)?.checked_sub(c.checked_mul(*d)?)?;
+        debug_assert_eq!(v % e, 0);
+        Some(v / e)
+    }
+
+    fn to_big(&self) -> BigInt {
+        let m = BigInt::from(self.unsigned_abs());
+        if *self < 0 { -m } else { m }
+    }
+}
+
+impl Ring for BigInt {
+    fn from_i64(v: i64) -> BigInt {
+        BigInt::from(v)
+    }
+
+    fn is_zero(&self) -> bool {
+        BigInt::is_zero(self)
+    }
+
+    fn cross(a: &BigInt, b: &BigInt, c: &BigInt, d: &BigInt, e: &BigInt) -> Option<BigInt> {
+        let (q, r) = (a * b - c * d).div_rem(e)?;
+        debug_assert!(r.is_zero());
+        Some(q)
+    }
+
+    fn to_big(&self) -> BigInt {
+        self.clone()
+    }
+}
+
+// Fraction-free Gauss-Jordan: afterwards every pivot row is zero in all other
+// pivot columns. Returns the pivot columns in row order.
+fn bareiss<T: Ring>(m: &mut [Vec<T>], cols: usize) -> Option<Vec<usize>> {
+    let mut pivots = Vec::new();
+    let mut prev = T::from_i64(1);
+    for col in 0..cols {
+        let r = pivots.len();
+        let Some(p) = (r..m.len()).find(|&i| !m[i][col].is_zero()) else {
+            continue;
+        };
+        m.swap(r, p);
+        let pivot = m[r][col].clone();
+        for i in 0..m.len() {
+            if i == r {
+                continue;
+            }
+            let f = m[i][col].clone();
+            for j in 0..m[i].len() {
+                m[i][j] = T::cross(&pivot, &m[i][j], &f, &m[r][j], &prev)?;
+            }
+        }
+        prev = pivot;
+        pivots.push(col);
+        if pivots.len() == m.len() {
+            break;
+        }
+    }
+    Some(pivots)
+}
+
+fn eliminate(a: &[Vec<i64>], cols: usize) -> (Vec<Vec<BigInt>>, Vec<usize>) {
+    let mut small: Vec<Vec<i128>> = a.iter().map(|r| r.iter().map(|&v| v as i128).collect()).collect();
+    if let Some(pivots) = bareiss(&mut small, cols) {
+        let rows = small.iter().map(|r| r.iter().map(Ring::to_big).collect()).collect();
+        return (rows, pivots);
+    }
+    let mut big: Vec<Vec<BigInt>> = a.iter().map(|r| r.iter().map(|&v| BigInt::from(v)).collect()).collect();
+    let pivots = bareiss(&mut big, cols).expect("bigint elimination cannot overflow");
+    (big, pivots)
+}
+
+// Reduced row echelon form: `rows[k]` has a 1 in column `pivots[k]` and zeros
+// in every other pivot column. Zero rows are dropped, so rank = rows.len().
+#[derive(Clone, Debug)]
+pub struct Rref {
+    pub rows: Vec<Vec<Rational>>,
+    pub pivots: Vec<usize>,
+    pub cols: usize,
+}
+
+impl Rref {
+    pub fn rank(&self) -> usize {
+        self.pivots.len()
+    }
+
+    pub fn free_columns(&self) -> Vec<usize> {
+        (0..self.cols).filter(|c| !self.pivots.contains(c)).collect()
+    }
+}
+
+fn normalize(rows: Vec<Vec<BigInt>>, pivots: Vec<usize>, cols: usize) -> Rref {
+    let rows = rows
+        .into_iter()
+        .zip(&pivots)
+        .map(|(row, &p)| {
+            let d = row[p].clone();
+            row.into_iter().map(|v| Rational::new(v, d.clone())).collect()
+        })
+        .collect();
+    Rref { rows, pivots, cols }
+}
+
+pub fn rref(a: &[Vec<i64>]) -> Rref {
+    let cols = a.first().map_or(0, Vec::len);
+    let (rows, pivots) = eliminate(a, cols);
+    normalize(rows, pivots, cols)
+}
+
+pub fn rank(a: &[Vec<i64>]) -> usize {
+    rref(a).rank()
+}
+
+// Integer basis of { x : a x = 0 }, one vector per free column, each scaled
+// to the smallest integer multiple with a positive free entry.
+pub fn nullspace(a: &[Vec<i64>]) -> Vec<Vec<BigInt>> {
+    let r = rref(a);
+    r.free_columns()
+        .into_iter()
+        .map(|free| {
+            let mut v = vec![Rational::zero(); r.cols];
+            v[free] = Rational::from(1);
+            for (row, &p) in r.rows.iter().zip(&r.pivots) {
+                v[p] = Rational::zero().sub(&row[free]);
+            }
+            clear_denominators(&v)
+        })
+        .collect()
+}
+
+fn clear_denominators(v: &[Rational]) -> Vec<BigInt> {
+    let lcm = v.iter().fold(BigInt::one(), |acc, x| {
+        let g = acc.gcd(x.denom());
+        (&acc * x.denom()).div_rem(&g).unwrap().0
+    });
+    let scaled: Vec<BigInt> = v.iter().map(|x| (x.numer() * &lcm).div_rem(x.denom()).unwrap().0).collect();
+    let g = scaled.iter().fold(BigInt::zero(), |acc, x| acc.gcd(x));
+    if g.is_zero() {
+        return scaled;
+    }
+    scaled.iter().map(|x| x.div_rem(&g).unwrap().0).collect()
+}
+
+// What one elimination of [a | b] says about a x = b.
+#[derive(Clone, Debug)]
+pub struct Solved {
+    pub rank: usize,
+    // free variables set to zero
+    pub particular: Vec<Rational>,
+}
+
+// None if the system is inconsistent
+pub fn solve(a: &[Vec<i64>], b: &[i64]) -> Option<Solved> {
+    let cols = a.first().map_or(0, Vec::len);
+    let augmented: Vec<Vec<i64>> = a.iter().zip(b).map(|(row, &v)| {
+        let mut r = row.clone();
+        r.push(v);
+        r
+    }).collect();
+    let r = rref(&augmented);
+    if r.pivots.last() == Some(&cols) {
+        return None;
+    }
+    let mut particular = vec![Rational::zero(); cols];
+    for (row, &p) in r.rows.iter().zip(&r.pivots) {
+        particular[p] = row[cols].clone();
+    }
+    Some(Solved { rank: r.rank(), particular })
+}
+
+// Some x with a x = b (free variables set to zero), or None if the system is
+// inconsistent.
+pub fn particular_solution(a: &[Vec<i64>], b: &[i64]) -> Option<Vec<Rational>> {
+    solve(a, b).map(|s| s.particular)
+}
+
+// Cross-checks elimination on random matrices against plain i128 arithmetic:
+// rank against naive fraction-free elimination, and the nullspace and
+// particular solution by substituting them back. Every tenth matrix has
+// entries large enough to force the BigInt fallback; those are substituted
+// back in BigInt instead.
+pub fn self_check(cases: usize) -> Result<(), String> {
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move |bound: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % bound
+    };
+    for case in 0..cases {
+        let (m, n) = (next(6) as usize + 1, next(7) as usize + 1);
+        let large = case % 10 == 0;
+        let mut a: Vec<Vec<i64>> = (0..m)
+            .map(|_| (0..n).map(|_| {
+                if large { next(2_000_000_000_000) as i64 - 1_000_000_000_000 } else { next(7) as i64 - 3 }
+            }).collect())
+            .collect();
+        if m > 2 && case % 3 == 0 {
+            a[m - 1] = a[0].iter().zip(&a[1]).map(|(x, y)| 2 * x - 3 * y).collect();
+        }
+        let fail = |what: &str| Err(format!("linalg {} wrong for {:?}", what, a));
+
+        let r = rref(&a);
+        let nullspace = nullspace(&a);
+        if r.rank() + nullspace.len() != n {
+            return fail("rank + nullity");
+        }
+        for v in &nullspace {
+            for row in &a {
+                let dot: BigInt = row.iter().zip(v).map(|(&x, y)| BigInt::from(x) * y).sum();
+                if !dot.is_zero() {
+                    return fail("nullspace");
+                }
+            }
+        }
+        if large {
+            continue;
+        }
+
+        if r.rank() != naive_rank(&a) {
+            return fail("rank");
+        }
+        let x0: Vec<i64> = (0..n).map(|_| next(5) as i64).collect();
+        let b: Vec<i64> = a.iter().map(|row| row.iter().zip(&x0).map(|(p, q)| p * q).sum()).collect();
+        let Some(x) = particular_solution(&a, &b) else {
+            return fail("consistency");
+        };
+        // a x = b scaled by the common denominator, all in i128
+        let to_i128 = |v: &BigInt| v.to_i64().map(|v| v as i128);
+        let dens: Option<Vec<i128>> = x.iter().map(|v| to_i128(v.denom())).collect();
+        let nums: Option<Vec<i128>> = x.iter().map(|v| to_i128(v.numer())).collect();
+        let (Some(dens), Some(nums)) = (dens, nums) else {
+            return fail("solution size");
+        };
+        let lcm = dens.iter().fold(1i128, |l, &d| l / gcd_i128(l, d) * d);
+        for (row, &target) in a.iter().zip(&b) {
+            let lhs: i128 = row.iter().enumerate().map(|(j, &c)| c as i128 * nums[j] * (lcm / dens[j])).sum();
+            if lhs != target as i128 * lcm {
+                return fail("particular solution");
+            }
+        }
+    }
+    if particular_solution(&[vec![1, 1], vec![2, 2]], &[1, 3]).is_some() {
+        return Err("linalg accepted an inconsistent system".to_string());
+    }
+    Ok(())
+}
+
+fn gcd_i128(a: i128, b: i128) -> i128 {
+    if b == 0 { a.abs() } else { gcd_i128(b, a % b) }
+}
+
+// rank by elimination without any division; entries stay small enough for
+// i128 on the little matrices self_check uses
+fn naive_rank(a: &[Vec<i64>]) -> usize {
+    let mut m: Vec<Vec<i128>> = a.iter().map(|r| r.iter().map(|&v| v as i128).collect()).collect();
+    let cols = m.first().map_or(0, Vec::len);
+    let mut rank = 0;
+    for col in 0..cols {
+        let Some(p) = (rank..m.len()).find(|&i| m[i][col] != 0) else {
+            continue;
+        };
+        m.swap(rank, p);
+        for i in rank + 1..m.len() {
+            let (pv, f) = (m[rank][col], m[i][col]);
+            let pivot_row = m[rank].clone();
+            for (v, &p) in m[i].iter_mut().zip(&pivot_row) {
+                *v = *v * pv - p * f;
+            }
+            let g = m[i].iter().fold(0, |g, &v| gcd_i128(g, v));
+            if g > 1 {
+                m[i].iter_mut().for_each(|v| *v /= g);
+            }
+        }
+        rank += 1;
+    }
+    rank
+}